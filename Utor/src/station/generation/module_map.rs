@@ -2,7 +2,7 @@ use crate::station::config::*;
 use crate::station::room::Room;
 use corale::geom::{Cube, BoxCollider, BoundingBox};
 use corale::core::*;
-use rand::prelude::*;
+use vtor::seed::{Seed, Subsystem};
 use vek::Vec3;
 
 /// Contains a map of all interior structures of a module
@@ -11,8 +11,8 @@ pub struct ModuleMap<T, R> where T: OrdNum, R: Cube<T> + BoxCollider<T> {
     _var: T,
 }
 
-pub fn build<T>(bounds: &BoundingBox<T>, cfg: &Config) where T: OrdNum {
-    let mut rng = thread_rng();
+pub fn build<T>(bounds: &BoundingBox<T>, cfg: &Config, seed: Seed) where T: OrdNum {
+    let mut rng = seed.subsystem(Subsystem::Layout).rng();
     let mut spaces = Vec::new();
     let width = bounds.width().to_f64().unwrap();
     let height = bounds.height().to_f64().unwrap();
@@ -21,7 +21,7 @@ pub fn build<T>(bounds: &BoundingBox<T>, cfg: &Config) where T: OrdNum {
     // generate some big rooms
     let room_width =  rng.gen_range(width / 2., width);
     let room_length =  rng.gen_range(length / 16., length);
-    let room_height =  cfg.humans.dimensions().y;
+    let room_height =  cfg.humans.height() as f64;
 
     //calculate max point this room can fit in
     let pos_max = Vec3::new(width - room_width, height - room_height, length - room_length);
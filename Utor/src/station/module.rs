@@ -3,8 +3,9 @@ use corale::mesh::*;
 use corale::geom::*;
 
 use crate::filters::TorusModifier;
-use super::config::Config;
+use super::config::{Config, ModuleCfg};
 use super::generation::*;
+use vtor::seed::Seed;
 
 type Float = f64;
 
@@ -22,11 +23,11 @@ impl Module {
     /// theta: the angle size of the segment's arc
     /// radius: the radius from the ring center to the middle of the module
     /// mesh: parent mesh to clone from. 
-    pub fn new(index: usize, arc: Float, cfg: &Config, mesh: &Mesh<Float>) -> Self {
+    /// module: the module's own config, from `Config::module_cfg`, which also holds its seed
+    pub fn new(index: usize, arc: Float, cfg: &Config, module: &ModuleCfg, mesh: &Mesh<Float>) -> Self {
         let angle = index as Float * arc;
-        let mut torus_mod = TorusModifier::new(Vec2::zero(), angle, cfg.station.radius(), arc, 0., 0.);
-        let length = torus_mod.length();
-        torus_mod.set_size(length * cfg.modules.dimensions().x, length * cfg.modules.dimensions().y);
+        let mut torus_mod = TorusModifier::new(Vec2::zero(), angle, cfg.station.major::<Float>().unwrap(), arc, 0., 0.);
+        torus_mod.set_size(module.extent().w as Float, module.height as Float);
         let mut new_mesh = mesh.clone();
         let name = format!("module_{}", index);
         new_mesh.set_name(name);
@@ -35,17 +36,18 @@ impl Module {
         let bounds_max = Vec3::new(torus_mod.width(), torus_mod.height(), torus_mod.length());
         let mid_point = bounds_max / 2.;
 
+        let inner_space = module.inner_space().map(|v| v as Float);
         let offset = Vec3::new(
-            torus_mod.width() * cfg.modules.inner_space().x * 0.5, 
-            torus_mod.height() * cfg.modules.inner_space().y * 0.5, 
-            torus_mod.length() * cfg.modules.inner_space().z * 0.5, 
+            torus_mod.width() * inner_space.x * 0.5, 
+            torus_mod.height() * inner_space.y * 0.5, 
+            torus_mod.length() * inner_space.z * 0.5, 
         );
 
         let bounds = BoundingBox::new(mid_point - offset, mid_point + offset);
 
         println!("Bounds = {}m x {}m x {}m", bounds.width().floor(), bounds.height().floor(), bounds.depth().floor());
 
-        build(&bounds, &cfg);
+        build(&bounds, &cfg, Seed::new(module.seed));
 
         Self {
             index,
@@ -92,14 +94,14 @@ mod tests {
     
     #[test]
     fn module_test() {
-        let cfg = Config::import("assets/config.toml");
-        let angle = 2. * std::f64::consts::PI / cfg.station.wing_count() as f64 / cfg.wings.module_count()  as f64;
+        let cfg = Config::import("assets/config.toml").unwrap();
+        let angle = 2. * std::f64::consts::PI / cfg.station.sectors() as f64 / cfg.sectors.modules()  as f64;
         
         let file = File::open(format!("assets/module.obj")).unwrap();
         let input = BufReader::new(file);
         let mesh: Mesh<f64> = parse(input).unwrap();
         
-        let module = Module::new(0, angle, &cfg, &mesh);
+        let module = Module::new(0, angle, &cfg, &cfg.module_cfg(0, 0).unwrap(), &mesh);
         let build = module.build();
         let file_name = "../bin/renders/a_test".to_string();
         export(&build, file_name).unwrap();
@@ -9,20 +9,20 @@ pub struct Station {
 }
 
 impl Station {
-    pub fn new(cfg: Config) -> Self {
+    pub fn new(cfg: Config) -> Result<Self, ConfigError> {
         // cache a few resued vars
-        let wing_angle = (2. * std::f64::consts::PI as Float) / cfg.station.wing_count() as Float;
+        let wing_angle = (2. * std::f64::consts::PI as Float) / cfg.station.sectors() as Float;
         let mut wings = Vec::new();
 
         // Build each wing of the station
-        for i in 0..cfg.station.wing_count() {
-            let wing = Wing::new(i, wing_angle, &cfg);
+        for i in 0..cfg.station.sectors() {
+            let wing = Wing::new(i, wing_angle, &cfg)?;
             wings.push(wing);
         }
 
-        Self {
+        Ok(Self {
             wings,
-        }
+        })
     }
 
     pub fn build(&self) {
@@ -43,7 +43,7 @@ mod tests {
     
     #[test]
     fn station_test() {
-        let station = Station::new(Config::import("assets/config.toml"));
+        let station = Station::new(Config::import("assets/config.toml").unwrap()).unwrap();
         station.build();
     }
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use std::io::BufReader;
 use corale::wavefront::parse;
 use std::fs::File;
 use super::module::*;
-use super::config::Config;
+use super::config::{Config, ConfigError};
 
 type Float = f64;
 
@@ -12,9 +12,9 @@ pub struct Wing {
 }
 
 impl Wing {
-    pub(crate) fn new(index: usize, arc: Float, cfg: &Config) -> Self {
+    pub(crate) fn new(index: usize, arc: Float, cfg: &Config) -> Result<Self, ConfigError> {
         let angle = index as Float * arc;
-        let module_arc = arc / cfg.wings.module_count() as Float;
+        let module_arc = arc / cfg.sectors.modules() as Float;
         let mut modules = Vec::new();
         
         let file = File::open(format!("assets/module.obj")).unwrap();
@@ -22,13 +22,14 @@ impl Wing {
         let mesh = parse(input).unwrap();
 
         // Build the wing's modules
-        for m in 0..cfg.wings.module_count() {
-            let module = Module::new(3 * index + m, module_arc, &cfg, &mesh);
+        for m in 0..cfg.sectors.modules() {
+            let ring_index = index * cfg.sectors.modules() + m;
+            let module = Module::new(ring_index, module_arc, &cfg, &cfg.module_cfg(index, m)?, &mesh);
             modules.push(module);
         }
-        Self {
+        Ok(Self {
             _angle: angle,
             modules,
-        }
+        })
     }
 }
\ No newline at end of file
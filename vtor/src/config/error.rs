@@ -0,0 +1,144 @@
+use std::fmt;
+use std::io;
+
+/// A single problem found while validating a config, addressed by its dotted field path (e.g. `modules.room_count`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    pub field: String,
+    pub reason: String,
+}
+
+impl ConfigProblem {
+    pub fn new<S>(field: S, reason: S) -> Self where S: Into<String> {
+        Self {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read
+    Io { file: String, source: io::Error },
+    /// The file is not valid toml, or does not match the expected layout
+    Parse { file: String, source: toml::de::Error },
+    /// The file parsed, but one or more values make no sense
+    Invalid { file: String, problems: Vec<ConfigProblem> },
+}
+
+impl ConfigError {
+    pub fn file(&self) -> &str {
+        match self {
+            ConfigError::Io { file, .. } => file,
+            ConfigError::Parse { file, .. } => file,
+            ConfigError::Invalid { file, .. } => file,
+        }
+    }
+
+    /// All semantic problems found. Empty for io and parse errors.
+    pub fn problems(&self) -> &[ConfigProblem] {
+        match self {
+            ConfigError::Invalid { problems, .. } => problems,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { file, source } => write!(f, "{}: unable to read file: {}", file, source),
+            ConfigError::Parse { file, source } => write!(f, "{}: {}", file, source),
+            ConfigError::Invalid { file, problems } => {
+                write!(f, "{}: {} invalid value(s)", file, problems.len())?;
+                for problem in problems {
+                    write!(f, "\n    {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Collects every problem found during a validation pass, so they can all be reported at once.
+#[derive(Default)]
+pub(crate) struct Validator {
+    problems: Vec<ConfigProblem>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a problem against `path.field` if the condition does not hold
+    pub fn check(&mut self, condition: bool, path: &str, field: &str, reason: &str) {
+        if !condition {
+            self.problems.push(ConfigProblem::new(join_path(path, field), reason.to_string()));
+        }
+    }
+
+    pub fn push(&mut self, problem: ConfigProblem) {
+        self.problems.push(problem);
+    }
+
+    /// Takes every problem from another pass that has not already been recorded
+    pub fn merge(&mut self, other: Validator) {
+        for problem in other.problems {
+            if !self.problems.contains(&problem) {
+                self.problems.push(problem);
+            }
+        }
+    }
+
+    pub fn at_least<N>(&mut self, path: &str, field: &str, value: N, min: N) where N: PartialOrd + fmt::Display {
+        let reason = format!("must be at least {} (found {})", min, value);
+        self.check(value >= min, path, field, &reason);
+    }
+
+    pub fn positive<N>(&mut self, path: &str, field: &str, value: N) where N: PartialOrd + fmt::Display + Default {
+        let reason = format!("must be greater than zero (found {})", value);
+        self.check(value > N::default(), path, field, &reason);
+    }
+
+    /// Checks the value falls within [0, 1]
+    pub fn unit(&mut self, path: &str, field: &str, value: f32) {
+        let reason = format!("must be between 0 and 1 (found {})", value);
+        self.check((0. ..=1.).contains(&value), path, field, &reason);
+    }
+
+    pub fn finish(self) -> Result<(), Vec<ConfigProblem>> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(self.problems)
+        }
+    }
+}
+
+pub(crate) fn join_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else if field.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
@@ -1,9 +1,35 @@
 use serde::Deserialize;
+use super::error::Validator;
 
 #[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct HumanCfg {
     /// The minimum width required to fit a human
     width: f32,
     /// The minimum ceiling height required for human habbitation
     height: f32,
-}
\ No newline at end of file
+}
+
+impl Default for HumanCfg {
+    fn default() -> Self {
+        Self {
+            width: 1.2,
+            height: 2.2,
+        }
+    }
+}
+
+impl HumanCfg {
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub(crate) fn validate(&self, path: &str, v: &mut Validator) {
+        v.positive(path, "width", self.width);
+        v.positive(path, "height", self.height);
+    }
+}
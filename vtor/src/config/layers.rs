@@ -0,0 +1,251 @@
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml::value::{Table, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use super::error::{ConfigProblem, join_path};
+use super::param::Param;
+use crate::seed::Seed;
+
+/// The most ranged fields one stack of layers may hold. Every combination of their ends is validated, which doubles with each.
+pub const MAX_RANGED: usize = 10;
+
+/// Identifies where an effective config value came from. Later layers take precedence over earlier ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Layer {
+    /// Built-in default value
+    Default,
+    /// The base `[modules]` table
+    Base,
+    /// A `[sector.N.modules]` table, applied to every module in the sector
+    Sector(usize),
+    /// A `[sector.N.module.M]` table, applied to a single module
+    Module(usize, usize),
+}
+
+impl Layer {
+    /// The dotted path of the table this layer is read from
+    pub fn path(&self) -> String {
+        match self {
+            Layer::Default => "defaults".to_string(),
+            Layer::Base => "modules".to_string(),
+            Layer::Sector(s) => format!("sector.{}.modules", s),
+            Layer::Module(s, m) => format!("sector.{}.module.{}", s, m),
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.path())
+    }
+}
+
+/// An effective config value, along with the layer each of its fields was taken from.
+#[derive(Clone, Debug)]
+pub struct Resolved<C> {
+    pub value: C,
+    pub sources: BTreeMap<String, Layer>,
+    /// The distribution of every field that was drawn rather than fixed
+    pub params: BTreeMap<String, Param>,
+}
+
+impl<C> Resolved<C> {
+    pub fn source(&self, field: &str) -> Option<Layer> {
+        self.sources.get(field).copied()
+    }
+
+    pub fn param(&self, field: &str) -> Option<&Param> {
+        self.params.get(field)
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub(crate) struct RawSector {
+    #[serde(default)]
+    modules: Table,
+    #[serde(default)]
+    module: BTreeMap<String, Table>,
+}
+
+/// The raw, unmerged module tables as written in the config file.
+#[derive(Clone, Default)]
+pub(crate) struct ModuleLayers {
+    pub base: Table,
+    pub sectors: BTreeMap<usize, Table>,
+    pub modules: BTreeMap<(usize, usize), Table>,
+}
+
+impl ModuleLayers {
+    /// Sorts the `[sector]` tables by index. Keys that are not indices are reported as problems.
+    pub fn new(base: Table, raw: BTreeMap<String, RawSector>, problems: &mut Vec<ConfigProblem>) -> Self {
+        let mut layers = Self {
+            base,
+            ..Self::default()
+        };
+
+        for (key, sector) in raw {
+            let s = match key.parse::<usize>() {
+                Ok(s) => s,
+                Err(_) => {
+                    problems.push(ConfigProblem::new(format!("sector.{}", key), "sector must be addressed by index".to_string()));
+                    continue;
+                }
+            };
+            if !sector.modules.is_empty() {
+                layers.sectors.insert(s, sector.modules);
+            }
+            for (key, table) in sector.module {
+                match key.parse::<usize>() {
+                    Ok(m) => {
+                        layers.modules.insert((s, m), table);
+                    }
+                    Err(_) => problems.push(ConfigProblem::new(format!("sector.{}.module.{}", s, key), "module must be addressed by index".to_string())),
+                }
+            }
+        }
+        layers
+    }
+
+    /// Every override layer, sorted by address
+    pub fn overrides(&self) -> Vec<Layer> {
+        let mut layers: Vec<Layer> = self.sectors.keys().map(|s| Layer::Sector(*s)).collect();
+        layers.extend(self.modules.keys().map(|(s, m)| Layer::Module(*s, *m)));
+        layers
+    }
+
+    /// The base table alone, without any sector or module overrides
+    pub fn base_stack(&self) -> Vec<(Layer, &Table)> {
+        vec![(Layer::Base, &self.base)]
+    }
+
+    /// The tables that apply to the given module, from least to most specific
+    pub fn stack(&self, sector: usize, module: usize) -> Vec<(Layer, &Table)> {
+        let mut stack = vec![(Layer::Base, &self.base)];
+        if let Some(table) = self.sectors.get(&sector) {
+            stack.push((Layer::Sector(sector), table));
+        }
+        if let Some(table) = self.modules.get(&(sector, module)) {
+            stack.push((Layer::Module(sector, module), table));
+        }
+        stack
+    }
+}
+
+/// The layers of a config merged key by key, with any ranged values set aside to be drawn later.
+pub(crate) struct Merged {
+    table: Table,
+    sources: BTreeMap<String, Layer>,
+    params: BTreeMap<String, Param>,
+    top: Layer,
+}
+
+/// Merges each layer over the given defaults. Ranged values are replaced by their nominal value until drawn.
+pub(crate) fn merge<C>(defaults: &C, stack: &[(Layer, &Table)]) -> Result<Merged, ConfigProblem> where C: Serialize {
+    let defaults = match Value::try_from(defaults) {
+        Ok(Value::Table(table)) => table,
+        _ => panic!("config defaults must serialize to a table"),
+    };
+    let mut merged = defaults.clone();
+    let mut sources: BTreeMap<String, Layer> = merged.keys().map(|k| (k.clone(), Layer::Default)).collect();
+    let mut params = BTreeMap::new();
+    let mut top = Layer::Default;
+
+    for (layer, table) in stack {
+        for (key, value) in table.iter() {
+            let default = match defaults.get(key) {
+                Some(default) => default,
+                None => return Err(ConfigProblem::new(join_path(&layer.path(), key), "unknown field".to_string())),
+            };
+            match (value, default) {
+                (Value::Table(spec), d) if !d.is_table() => {
+                    let param = Param::from_table(spec).map_err(|reason| ConfigProblem::new(join_path(&layer.path(), key), reason))?;
+                    merged.insert(key.clone(), Param::to_value(param.nominal(), default));
+                    params.insert(key.clone(), param);
+                }
+                _ => {
+                    merged.insert(key.clone(), value.clone());
+                    params.remove(key);
+                }
+            }
+            sources.insert(key.clone(), *layer);
+        }
+        top = *layer;
+    }
+
+    Ok(Merged {
+        table: merged,
+        sources,
+        params,
+        top,
+    })
+}
+
+impl Merged {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.table.get(key)
+    }
+
+    /// True if any layer sets the key, rather than leaving it at its default
+    pub fn is_set(&self, key: &str) -> bool {
+        match self.sources.get(key) {
+            Some(layer) => *layer != Layer::Default,
+            None => false,
+        }
+    }
+
+    pub fn is_ranged(&self, key: &str) -> bool {
+        self.params.contains_key(key)
+    }
+
+    /// Uses the nominal value of every ranged field
+    pub fn nominal<C>(&self) -> Result<Resolved<C>, ConfigProblem> where C: DeserializeOwned {
+        self.resolve(self.table.clone())
+    }
+
+    /// Draws every ranged field. Each field gets its own stream below the given seed, so adding or removing one does not change what the others draw.
+    pub fn draw<C>(&self, seed: Seed) -> Result<Resolved<C>, ConfigProblem> where C: DeserializeOwned {
+        let mut table = self.table.clone();
+        for (key, param) in self.params.iter() {
+            let mut rng = seed.child(key).rng();
+            let x = param.draw(&mut rng);
+            table.insert(key.clone(), Param::to_value(x, &self.table[key]));
+        }
+        self.resolve(table)
+    }
+
+    /// One config for every combination of ends of the ranged fields, so a rule between two fields is met even when
+    /// both are drawn at their worst. More than `MAX_RANGED` ranged fields is a problem of its own.
+    pub fn extremes<C>(&self) -> Vec<Result<Resolved<C>, ConfigProblem>> where C: DeserializeOwned {
+        let params: Vec<(&String, (f64, f64))> = self.params.iter().map(|(key, param)| (key, param.bounds())).collect();
+        if params.is_empty() {
+            return Vec::new();
+        }
+        if params.len() > MAX_RANGED {
+            let reason = format!("ranges {} fields, at most {} can be checked together", params.len(), MAX_RANGED);
+            return vec![Err(ConfigProblem::new(self.top.path(), reason))];
+        }
+        let mut extremes = Vec::new();
+        for corner in 0..1usize << params.len() {
+            let mut table = self.table.clone();
+            for (i, (key, (low, high))) in params.iter().enumerate() {
+                let x = if corner & (1 << i) == 0 { *low } else { *high };
+                table.insert((*key).clone(), Param::to_value(x, &self.table[*key]));
+            }
+            extremes.push(self.resolve(table));
+        }
+        extremes
+    }
+
+    fn resolve<C>(&self, table: Table) -> Result<Resolved<C>, ConfigProblem> where C: DeserializeOwned {
+        match Value::Table(table).try_into() {
+            Ok(value) => Ok(Resolved {
+                value,
+                sources: self.sources.clone(),
+                params: self.params.clone(),
+            }),
+            Err(e) => Err(ConfigProblem::new(self.top.path(), e.to_string())),
+        }
+    }
+}
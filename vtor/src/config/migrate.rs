@@ -0,0 +1,273 @@
+use toml::value::{Table, Value};
+use std::f64::consts::PI;
+use super::error::ConfigProblem;
+use super::{StationCfg, SectorCfg};
+
+/// The schema version written by this crate. Bump it whenever a field is renamed or removed, and add a step to `migrate`.
+pub const SCHEMA_VERSION: i64 = 2;
+
+/// The layout a config file was written in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Schema {
+    /// Utor's original layout (`wings`, `radius`, `rooms.min_width`)
+    Utor,
+    /// vtor's original, unversioned layout (`sectors`, `major`, `minor`)
+    Vtor,
+    /// A versioned file
+    Versioned(i64),
+}
+
+/// A config upgraded to the current schema, with a warning for every value that could not be carried over.
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub from: Schema,
+    pub value: Value,
+    pub warnings: Vec<ConfigProblem>,
+}
+
+impl Migration {
+    /// The upgraded config, ready to be written back to disk
+    pub fn to_toml_string(&self) -> String {
+        toml::to_string(&self.value).expect("a parsed toml value should always serialize")
+    }
+}
+
+/// Works out which layout the given file was written in.
+pub fn detect(value: &Value) -> Schema {
+    if let Some(version) = value.get("version").and_then(Value::as_integer) {
+        return Schema::Versioned(version);
+    }
+    let station = value.get("station");
+    let utor = value.get("wings").is_some()
+        || value.get("rooms").is_some()
+        || station.and_then(|s| s.get("wings")).is_some()
+        || station.and_then(|s| s.get("radius")).is_some();
+    if utor {
+        Schema::Utor
+    } else {
+        Schema::Vtor
+    }
+}
+
+/// Upgrades a parsed config file of any known layout to the current schema.
+pub fn migrate(value: Value) -> Result<Migration, ConfigProblem> {
+    let from = detect(&value);
+    let mut table = match value {
+        Value::Table(table) => table,
+        _ => return Err(ConfigProblem::new("", "config must be a table")),
+    };
+    let mut warnings = Vec::new();
+
+    match from {
+        Schema::Utor => table = from_utor(table, &mut warnings),
+        Schema::Vtor | Schema::Versioned(1) => {}
+        Schema::Versioned(SCHEMA_VERSION) => {}
+        Schema::Versioned(v) => {
+            let reason = if v > SCHEMA_VERSION {
+                format!("schema version {} is newer than this build supports (1 to {})", v, SCHEMA_VERSION)
+            } else {
+                format!("unsupported schema version {} (supported versions are 1 to {})", v, SCHEMA_VERSION)
+            };
+            return Err(ConfigProblem::new("version".to_string(), reason));
+        }
+    }
+
+    if from != Schema::Versioned(SCHEMA_VERSION) {
+        station_seed(&mut table);
+
+        // Anything left over has no place in the current schema
+        let known = ["version", "station", "sectors", "modules", "humans", "paths", "sector"];
+        let dropped: Vec<String> = table.keys().filter(|k| !known.contains(&k.as_str())).cloned().collect();
+        for key in dropped {
+            table.remove(&key);
+            warnings.push(ConfigProblem::new(key, "dropped: not part of the current schema".to_string()));
+        }
+        table.insert("version".to_string(), Value::Integer(SCHEMA_VERSION));
+    }
+
+    Ok(Migration {
+        from,
+        value: Value::Table(table),
+        warnings,
+    })
+}
+
+fn from_utor(mut old: Table, warnings: &mut Vec<ConfigProblem>) -> Table {
+    let mut station = take_table(&mut old, "station");
+    let mut wings = take_table(&mut old, "wings");
+    let mut modules = take_table(&mut old, "modules");
+    let mut rooms = take_table(&mut old, "rooms");
+    let humans = take_table(&mut old, "humans");
+
+    let mut new_station = Table::new();
+    let mut new_sectors = Table::new();
+    let mut new_modules = Table::new();
+
+    move_value(&mut station, "wings", &mut new_station, "sectors");
+    move_value(&mut station, "radius", &mut new_station, "major");
+    move_value(&mut wings, "modules", &mut new_sectors, "modules");
+
+    // Utor sized modules relative to their length, the current schema uses metres
+    let sector_count = as_f64(new_station.get("sectors")).unwrap_or(StationCfg::default().sectors() as f64);
+    let module_count = as_f64(new_sectors.get("modules")).unwrap_or(SectorCfg::default().modules() as f64);
+    let radius = as_f64(new_station.get("major")).unwrap_or(StationCfg::default().major::<f64>().unwrap());
+    let length = 2. * radius * (PI / (sector_count * module_count)).sin();
+
+    if let Some(rooms) = modules.remove("rooms") {
+        if let Some(count) = rooms.as_integer() {
+            new_modules.insert("divisions".to_string(), Value::Integer(count * 2));
+        }
+        new_modules.insert("room_count".to_string(), rooms);
+    }
+    if let Some(width) = as_f64(modules.remove("width").as_ref()) {
+        let extent = vec![Value::Float(width * length), Value::Float(length)];
+        new_modules.insert("extent".to_string(), Value::Array(extent));
+    }
+    if let Some(height) = as_f64(modules.remove("height").as_ref()) {
+        new_modules.insert("height".to_string(), Value::Float(height * length));
+    }
+    move_value(&mut modules, "inner_space", &mut new_modules, "inner_space");
+
+    move_value(&mut rooms, "min_width", &mut new_modules, "min_room_width");
+
+    for (name, leftover) in [("station", station), ("wings", wings), ("modules", modules), ("rooms", rooms)].iter() {
+        for key in leftover.keys() {
+            warnings.push(ConfigProblem::new(format!("{}.{}", name, key), "dropped: not part of the current schema".to_string()));
+        }
+    }
+
+    let mut new = Table::new();
+    new.insert("station".to_string(), Value::Table(new_station));
+    new.insert("sectors".to_string(), Value::Table(new_sectors));
+    new.insert("modules".to_string(), Value::Table(new_modules));
+    new.insert("humans".to_string(), Value::Table(humans));
+    // Anything else is reported by the caller
+    new.extend(old);
+    new
+}
+
+/// Version 2 derives every module's seed from the station's, so a base seed becomes the station seed
+fn station_seed(table: &mut Table) {
+    let seed = match table.get_mut("modules") {
+        Some(Value::Table(modules)) => modules.remove("seed"),
+        _ => None,
+    };
+    if let Some(seed) = seed {
+        if let Value::Table(station) = table.entry("station".to_string()).or_insert_with(|| Value::Table(Table::new())) {
+            station.insert("seed".to_string(), seed);
+        }
+    }
+}
+
+fn take_table(table: &mut Table, key: &str) -> Table {
+    match table.remove(key) {
+        Some(Value::Table(t)) => t,
+        _ => Table::new(),
+    }
+}
+
+fn move_value(from: &mut Table, from_key: &str, to: &mut Table, to_key: &str) {
+    if let Some(value) = from.remove(from_key) {
+        to.insert(to_key.to_string(), value);
+    }
+}
+
+fn as_f64(value: Option<&Value>) -> Option<f64> {
+    match value {
+        Some(Value::Float(f)) => Some(*f),
+        Some(Value::Integer(i)) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+#[test]
+fn migrate_utor_test() {
+    let old: Value = toml::from_str(r#"
+        [station]
+        wings = 6
+        radius = 800
+
+        [wings]
+        modules = 3
+
+        [modules]
+        rooms = 20
+        width = 0.33
+        height = 0.33
+        inner_space = [0.5, 0.5, 1.0]
+
+        [rooms]
+        min_width = 4
+
+        [humans]
+        height = 2.4
+        width = 1.2
+    "#).unwrap();
+
+    let migration = migrate(old).unwrap();
+    assert_eq!(migration.from, Schema::Utor);
+    assert!(migration.warnings.is_empty());
+
+    let value = migration.value;
+    assert_eq!(value["version"].as_integer(), Some(SCHEMA_VERSION));
+    assert_eq!(value["station"]["sectors"].as_integer(), Some(6));
+    assert_eq!(value["sectors"]["modules"].as_integer(), Some(3));
+    assert_eq!(value["modules"]["room_count"].as_integer(), Some(20));
+    assert_eq!(value["modules"]["min_room_width"].as_integer(), Some(4));
+    let length = value["modules"]["extent"][1].as_float().unwrap();
+    assert!((length - 277.8).abs() < 0.1);
+}
+
+#[test]
+fn migrate_detect_test() {
+    let old: Value = toml::from_str(r#"
+        [station]
+        sectors = 6
+
+        [rooms]
+        min_width = 4
+    "#).unwrap();
+
+    let migration = migrate(old).unwrap();
+    assert_eq!(migration.from, Schema::Utor);
+
+    let old: Value = toml::from_str(r#"
+        [station]
+        sectors = 6
+
+        [corridors]
+        width = 4
+    "#).unwrap();
+
+    let migration = migrate(old).unwrap();
+    assert_eq!(migration.from, Schema::Vtor);
+    assert_eq!(migration.warnings, vec![ConfigProblem::new("corridors", "dropped: not part of the current schema")]);
+    let newer = migrate(toml::from_str("version = 99").unwrap()).unwrap_err();
+    assert!(newer.reason.contains("newer"), "{}", newer.reason);
+    let older = migrate(toml::from_str("version = 0").unwrap()).unwrap_err();
+    assert!(older.reason.contains("1 to"), "{}", older.reason);
+}
+
+#[test]
+fn migrate_seed_test() {
+    let old: Value = toml::from_str(r#"
+        version = 1
+
+        [modules]
+        seed = 7
+
+        [sector.1.module.2]
+        seed = 9
+    "#).unwrap();
+
+    let migration = migrate(old).unwrap();
+    assert_eq!(migration.from, Schema::Versioned(1));
+    let value = migration.value;
+    assert_eq!(value["station"]["seed"].as_integer(), Some(7));
+    assert!(value["modules"].get("seed").is_none());
+    // Seeds set on a single module still pin it
+    assert_eq!(value["sector"]["1"]["module"]["2"]["seed"].as_integer(), Some(9));
+
+    let current: Value = toml::from_str("version = 2\n[modules]\nseed = 7").unwrap();
+    assert_eq!(migrate(current).unwrap().value["modules"]["seed"].as_integer(), Some(7));
+}
@@ -2,34 +2,502 @@ mod station_cfg;
 mod sector_cfg;
 mod module_cfg;
 mod human_cfg;
+mod path_cfg;
+mod error;
+mod layers;
+mod migrate;
+mod param;
+mod overrides;
+pub mod presets;
 
 pub use station_cfg::StationCfg;
 pub use sector_cfg::SectorCfg;
-pub use module_cfg::ModuleCfg;
+pub use module_cfg::{ModuleCfg, LayoutMode, Mirror};
 pub use human_cfg::HumanCfg;
+pub use path_cfg::PathCfg;
+pub use error::{ConfigError, ConfigProblem};
+pub use layers::{Layer, Resolved, MAX_RANGED};
+pub use param::Param;
+pub use overrides::Override;
+pub use migrate::{migrate, Migration, Schema, SCHEMA_VERSION};
 
+use error::Validator;
+use crate::seed::{Seed, Subsystem};
+use layers::{ModuleLayers, RawSector};
 use serde::Deserialize;
+use toml::value::{Table, Value};
+use std::collections::BTreeMap;
 use std::fs;
 
-#[derive(Deserialize, Clone)]
+/// The config file as written, before any layers are merged
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    #[allow(dead_code)]
+    version: i64,
+    #[serde(default)]
+    station: StationCfg,
+    #[serde(default)]
+    sectors: SectorCfg,
+    #[serde(default)]
+    modules: Table,
+    #[serde(default)]
+    humans: HumanCfg,
+    #[serde(default)]
+    paths: PathCfg,
+    #[serde(default)]
+    sector: BTreeMap<String, RawSector>,
+}
+
+/// Where a config is read from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    File(String),
+    /// One of the built-in presets, see `presets::names`
+    Preset(String),
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub station: StationCfg,
     pub sectors: SectorCfg,
+    /// The base module config: defaults with `[modules]` applied. Use `module_cfg` to include sector and module overrides.
     pub modules: ModuleCfg,
     pub humans: HumanCfg,
+    pub paths: PathCfg,
+    layers: ModuleLayers,
+    warnings: Vec<ConfigProblem>,
+    /// Where the config was read from, for error reporting
+    file: String,
 }
 
 
 impl Config {
-    pub fn import(file: &str) -> Self {
-        let file_string = fs::read_to_string(file).unwrap();
-        let parsed: Self = toml::from_str(&file_string).unwrap();
-        parsed
+    /// Reads, parses and validates the given config file.
+    pub fn import(file: &str) -> Result<Self, ConfigError> {
+        Self::load(&Source::File(file.to_string()), &[] as &[&str])
+    }
+
+    /// Loads a built-in preset by name.
+    pub fn preset(name: &str) -> Result<Self, ConfigError> {
+        Self::load(&Source::Preset(name.to_string()), &[] as &[&str])
+    }
+
+    /// Loads a file or preset, then applies `key=value` overrides on top. Overrides get the same validation as file values.
+    pub fn load<S>(source: &Source, overrides: &[S]) -> Result<Self, ConfigError> where S: AsRef<str> {
+        match source {
+            Source::File(file) => {
+                let file_string = fs::read_to_string(file).map_err(|source| ConfigError::Io {
+                    file: file.to_string(),
+                    source,
+                })?;
+                Self::parse_with(&file_string, file, overrides)
+            }
+            Source::Preset(name) => match presets::find(name) {
+                Some((name, text)) => Self::parse_with(text, &format!("preset:{}", name), overrides),
+                None => {
+                    let reason = format!("unknown preset (available: {})", presets::names().join(", "));
+                    Err(ConfigError::Invalid {
+                        file: format!("preset:{}", name),
+                        problems: vec![ConfigProblem::new("preset".to_string(), reason)],
+                    })
+                }
+            },
+        }
+    }
+
+    /// Builds a config from command line arguments: an optional `--config <file>` or `--preset <name>`, followed by any
+    /// number of `key=value` overrides. Without a source, the `standard` preset is used.
+    pub fn from_args<I>(args: I) -> Result<Self, ConfigError> where I: IntoIterator<Item = String> {
+        let mut source = Source::Preset("standard".to_string());
+        let mut overrides = Vec::new();
+        let mut problems = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "--preset" => match args.next() {
+                    Some(value) if arg == "--config" => source = Source::File(value),
+                    Some(value) => source = Source::Preset(value),
+                    None => problems.push(ConfigProblem::new(arg.clone(), "expected a value".to_string())),
+                },
+                _ if arg.contains('=') => overrides.push(arg),
+                _ => problems.push(ConfigProblem::new(arg.clone(), "expected --config, --preset or key=value".to_string())),
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid {
+                file: "<args>".to_string(),
+                problems,
+            });
+        }
+        Self::load(&source, &overrides)
+    }
+
+    /// Parses and validates config text, upgrading older layouts to the current schema. `file` is only used for error reporting.
+    pub fn parse(text: &str, file: &str) -> Result<Self, ConfigError> {
+        Self::parse_with(text, file, &[] as &[&str])
+    }
+
+    /// As `parse`, with `key=value` overrides applied after the file is upgraded to the current schema.
+    pub fn parse_with<S>(text: &str, file: &str, overrides: &[S]) -> Result<Self, ConfigError> where S: AsRef<str> {
+        let parse_error = |source| ConfigError::Parse {
+            file: file.to_string(),
+            source,
+        };
+        let invalid = |problems| ConfigError::Invalid {
+            file: file.to_string(),
+            problems,
+        };
+
+        let value: Value = toml::from_str(text).map_err(parse_error)?;
+        let migration = migrate(value).map_err(|problem| invalid(vec![problem]))?;
+        let mut table = match migration.value {
+            Value::Table(table) => table,
+            _ => unreachable!("migrate only accepts tables"),
+        };
+
+        let mut problems = Vec::new();
+        for text in overrides {
+            if let Err(problem) = Override::parse(text.as_ref()).and_then(|o| o.apply(&mut table)) {
+                problems.push(problem);
+            }
+        }
+        if !problems.is_empty() {
+            return Err(invalid(problems));
+        }
+
+        let raw: RawConfig = Value::Table(table).try_into().map_err(parse_error)?;
+
+        let layers = ModuleLayers::new(raw.modules, raw.sector, &mut problems);
+        let modules = match layers::merge(&ModuleCfg::default(), &layers.base_stack()).and_then(|m| m.nominal()) {
+            Ok(resolved) => resolved.value,
+            Err(problem) => {
+                problems.push(problem);
+                return Err(invalid(problems));
+            }
+        };
+
+        let parsed = Self {
+            station: raw.station,
+            sectors: raw.sectors,
+            modules,
+            humans: raw.humans,
+            paths: raw.paths,
+            layers,
+            warnings: migration.warnings,
+            file: file.to_string(),
+        };
+
+        if let Err(mut errors) = parsed.validate() {
+            problems.append(&mut errors);
+        }
+        if problems.is_empty() {
+            Ok(parsed)
+        } else {
+            Err(invalid(problems))
+        }
+    }
+
+    /// Checks every value for range and cross-field problems, returning all of them rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<ConfigProblem>> {
+        let mut v = Validator::new();
+        self.station.validate("station", &mut v);
+        self.sectors.validate("sectors", &mut v);
+        self.validate_module(&self.modules, "modules", &mut v);
+        self.humans.validate("humans", &mut v);
+        self.paths.validate("paths", &mut v);
+
+        // Each override is checked once, as it applies to the first module it touches
+        let mut checks = vec![(Layer::Base, self.layers.base_stack())];
+        for layer in self.layers.overrides() {
+            let (sector, module) = match layer {
+                Layer::Sector(s) => (s, self.first_plain_module(s)),
+                Layer::Module(s, m) => (s, m),
+                _ => continue,
+            };
+            v.check(sector < self.station.sectors(), &layer.path(), "", "no such sector");
+            v.check(module < self.sectors.modules(), &layer.path(), "", "no such module");
+            checks.push((layer, self.layers.stack(sector, module)));
+        }
+
+        for (layer, stack) in checks {
+            let path = layer.path();
+            let merged = match layers::merge(&ModuleCfg::default(), &stack) {
+                Ok(merged) => merged,
+                Err(problem) => {
+                    v.push(problem);
+                    continue;
+                }
+            };
+            v.check(!merged.is_ranged("seed"), &path, "seed", "cannot be drawn from a range");
+            // A seed shared by every module would make them all the same
+            let reason = "would give every module the same layout; set [station] seed, or a seed per sector or module";
+            v.check(layer != Layer::Base || !merged.is_set("seed"), &path, "seed", reason);
+
+            // The base config is already resolved as `self.modules`
+            let mut resolved = if layer == Layer::Base { Vec::new() } else { vec![merged.nominal()] };
+            // Ranged values are checked at every combination of their ends, so that every draw is valid
+            resolved.append(&mut merged.extremes());
+            for cfg in resolved {
+                match cfg {
+                    Ok(cfg) => {
+                        let mut extreme = Validator::new();
+                        self.validate_module(&cfg.value, &path, &mut extreme);
+                        v.merge(extreme);
+                    }
+                    Err(problem) => v.push(problem),
+                }
+            }
+        }
+        v.finish()
+    }
+
+    fn validate_module(&self, cfg: &ModuleCfg, path: &str, v: &mut Validator) {
+        cfg.validate(path, v);
+        let reason = format!("module is too narrow to fit a human ({}m)", self.humans.width());
+        v.check(cfg.extent().w >= self.humans.width(), path, "extent[0]", &reason);
+        let reason = format!("module is too low to fit a human ({}m)", self.humans.height());
+        v.check(cfg.height >= self.humans.height(), path, "height", &reason);
+    }
+
+    /// Values that were dropped while upgrading an older file to the current schema
+    pub fn warnings(&self) -> &[ConfigProblem] {
+        &self.warnings
+    }
+
+    /// The first module in the sector without an override of its own, so sector overrides are checked in isolation
+    fn first_plain_module(&self, sector: usize) -> usize {
+        (0..self.sectors.modules()).find(|m| !self.layers.modules.contains_key(&(sector, *m))).unwrap_or(0)
+    }
+
+    /// The effective config of a single module, with the layer each value was taken from. Ranged values are drawn from the module's seed.
+    pub fn resolve_module(&self, sector: usize, module: usize) -> Result<Resolved<ModuleCfg>, ConfigError> {
+        let invalid = |problem| ConfigError::Invalid {
+            file: self.file.clone(),
+            problems: vec![problem],
+        };
+        let merged = layers::merge(&ModuleCfg::default(), &self.layers.stack(sector, module)).map_err(invalid)?;
+        let seed = self.seed_of(&merged, sector, module);
+        let mut resolved: Resolved<ModuleCfg> = merged.draw(seed.subsystem(Subsystem::Config)).map_err(invalid)?;
+        resolved.value.seed = seed.value();
+        Ok(resolved)
+    }
+
+    /// The seed every random draw for the given module is derived from
+    pub fn module_seed(&self, sector: usize, module: usize) -> Result<Seed, ConfigError> {
+        self.module_cfg(sector, module).map(|cfg| Seed::new(cfg.seed))
+    }
+
+    /// A seed set for the module or its sector pins the module, otherwise it is derived from the station seed and the module's address.
+    /// Validation rejects a seed in the base layer, which would pin every module to the same one.
+    fn seed_of(&self, merged: &layers::Merged, sector: usize, module: usize) -> Seed {
+        match merged.get("seed").and_then(|s| s.as_integer()) {
+            Some(seed) if merged.is_set("seed") => Seed::new(seed as u64),
+            _ => self.station.seed().sector(sector).module(module),
+        }
+    }
+
+    /// The effective config of a single module
+    pub fn module_cfg(&self, sector: usize, module: usize) -> Result<ModuleCfg, ConfigError> {
+        self.resolve_module(sector, module).map(|resolved| resolved.value)
     }
 }
 
 
 #[test]
 fn config_test() {
-    Config::import("assets/World.toml");
-}
\ No newline at end of file
+    let cfg = Config::import("assets/World.toml").unwrap();
+    assert!(cfg.warnings().is_empty());
+}
+
+#[test]
+fn config_validation_test() {
+    let err = Config::parse(r#"
+        [station]
+        sectors = 0
+        major = 800.0
+        minor = 32.0
+
+        [sectors]
+        modules = 3
+
+        [modules]
+        seed = 1
+        room_count = 20
+        divisions = 10
+        extent = [64.0, 128.0]
+        divide_area_chance = 0.55
+        divide_disparity_chance = 0.1
+        split_offset = 0.4
+        split_degredation = 0.9
+
+        [humans]
+        width = 1.2
+        height = 2.2
+    "#, "test").err().unwrap();
+
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["station.sectors", "modules.room_count"]);
+
+    let mut cfg = Config::import("assets/World.toml").unwrap();
+    cfg.modules.split_offset = 0.5;
+    cfg.humans = toml::from_str("width = 100.0").unwrap();
+    let problems = cfg.validate().unwrap_err();
+    let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec![
+        "modules.split_offset",
+        "modules.extent[0]",
+        "sector.0.modules.extent[0]",
+        "sector.0.module.1.extent[0]",
+    ]);
+
+    // An offset that fits the whole width can fall outside the half a mirrored spine is laid out in
+    let spine = |mirror: &str| Config::parse(&format!(r#"
+        version = 2
+
+        [modules]
+        layout = "spine"
+        mirror = "{}"
+        extent = [32.0, 128.0]
+        spine_offset = 10.0
+    "#, mirror), "test");
+    assert!(spine("none").is_ok());
+    let err = spine("lateral").err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["modules.spine_offset"]);
+
+    // Each range fits on its own, but not when many rooms are drawn alongside few divisions
+    let err = Config::parse(r#"
+        version = 2
+
+        [sector.0.modules]
+        room_count = { min = 10, max = 20 }
+        divisions = { min = 18, max = 40 }
+    "#, "test").err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["sector.0.modules.room_count"]);
+}
+
+
+#[test]
+fn config_layers_test() {
+    let cfg = Config::parse(r#"
+        version = 2
+
+        [sector.0.modules]
+        seed = 1
+
+        [sector.2.modules]
+        room_count = 8
+
+        [sector.2.module.1]
+        seed = 42
+    "#, "test").unwrap();
+
+    let base = cfg.resolve_module(0, 1).unwrap();
+    assert_eq!(base.value.seed, 1);
+    assert_eq!(base.source("seed"), Some(Layer::Sector(0)));
+    assert_eq!(base.source("room_count"), Some(Layer::Default));
+
+    let module = cfg.resolve_module(2, 1).unwrap();
+    assert_eq!(module.value.seed, 42);
+    assert_eq!(module.value.room_count, 8);
+    assert_eq!(module.source("seed"), Some(Layer::Module(2, 1)));
+    assert_eq!(module.source("room_count"), Some(Layer::Sector(2)));
+
+    // Without a seed of its own, a module's seed comes from the station and its address
+    let cfg = Config::parse("version = 2\n[station]\nseed = 5\n[sector.1.module.0]\nseed = 9", "test").unwrap();
+    assert_eq!(cfg.module_seed(0, 1).unwrap(), Seed::new(5).sector(0).module(1));
+    assert_eq!(cfg.module_seed(1, 0).unwrap(), Seed::new(9));
+    assert_eq!(cfg.resolve_module(0, 1).unwrap().source("seed"), Some(Layer::Default));
+
+    // A seed for every module at once is refused, as every module would come out the same
+    let err = Config::parse("version = 2\n[modules]\nseed = 1", "test").err().unwrap();
+    assert_eq!(err.problems()[0].field, "modules.seed");
+
+    let err = Config::parse(r#"
+        [sector.9.modules]
+        room_cont = 8
+    "#, "test").err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["sector.9.modules", "sector.9.modules.room_cont"]);
+}
+
+#[test]
+fn config_param_test() {
+    let cfg = Config::parse(r#"
+        [modules]
+        seed = 1
+        room_count = { min = 10, max = 20 }
+        divisions = 40
+        split_offset = { normal = [0.4, 0.05], max = 0.45 }
+    "#, "test").unwrap();
+
+    assert_eq!(cfg.modules.room_count, 15);
+    let a = cfg.resolve_module(0, 0).unwrap();
+    let b = cfg.resolve_module(0, 0).unwrap();
+    assert_eq!(a.value.room_count, b.value.room_count);
+    assert_eq!(a.param("room_count"), Some(&Param::Range { min: 10., max: 20. }));
+    assert!(a.param("seed").is_none());
+
+    let counts: Vec<usize> = (0..6).map(|s| cfg.module_cfg(s, 0).unwrap().room_count).collect();
+    assert!(counts.iter().all(|c| *c >= 10 && *c <= 20));
+    assert!(counts.iter().any(|c| *c != counts[0]));
+
+    let err = Config::parse(r#"
+        [modules]
+        room_count = { min = 10, max = 30 }
+        divisions = 24
+    "#, "test").err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["modules.room_count"]);
+
+    // Too many ranged fields to check every combination of, without overflowing the count
+    let err = Config::parse(r#"
+        [sector.0.modules]
+        divide_area_chance = { min = 0.1, max = 0.2 }
+        divide_disparity_chance = { min = 0.1, max = 0.2 }
+        split_offset = { min = 0.1, max = 0.2 }
+        split_degredation = { min = 0.1, max = 0.2 }
+        door_frame = { min = 0.1, max = 0.2 }
+        loop_factor = { min = 0.1, max = 0.2 }
+        merge_rate = { min = 0.1, max = 0.2 }
+        min_room_width = { min = 2, max = 3 }
+        min_room_depth = { min = 2, max = 3 }
+        max_aspect_ratio = { min = 3, max = 4 }
+        height = { min = 3, max = 4 }
+    "#, "test").err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["sector.0.modules"]);
+    assert!(err.problems()[0].reason.contains(&MAX_RANGED.to_string()));
+}
+
+#[test]
+fn config_preset_test() {
+    for name in presets::names() {
+        Config::preset(name).unwrap();
+    }
+    let cfg = Config::preset("Small Outpost").unwrap();
+    assert_eq!(cfg.station.sectors(), 3);
+    assert!(Config::preset("moon_base").is_err());
+
+    let args = vec!["--preset", "small_outpost", "station.seed=42", "station.sectors=8", "modules.layout=treemap"];
+    let cfg = Config::from_args(args.into_iter().map(String::from)).unwrap();
+    assert_eq!(cfg.module_seed(0, 0).unwrap(), Seed::new(42).sector(0).module(0));
+    assert_eq!(cfg.station.sectors(), 8);
+    assert_eq!(cfg.module_cfg(0, 0).unwrap().layout, LayoutMode::Treemap);
+
+    let overrides = ["modules.room_count=100", "station.sector=8", "modules"];
+    let err = Config::load(&Source::File("assets/World.toml".to_string()), &overrides).err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["modules"]);
+
+    let err = Config::load(&Source::File("assets/World.toml".to_string()), &overrides[..2]).err().unwrap();
+    assert!(matches!(err, ConfigError::Parse { .. }));
+
+    let err = Config::load(&Source::File("assets/World.toml".to_string()), &overrides[..1]).err().unwrap();
+    let fields: Vec<&str> = err.problems().iter().map(|p| p.field.as_str()).collect();
+    assert_eq!(fields, vec!["modules.room_count"]);
+}
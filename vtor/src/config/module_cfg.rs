@@ -1,33 +1,166 @@
-use vek::Extent2;
-use serde::Deserialize;
+use vek::{Extent2, Vec3};
+use serde::{Deserialize, Serialize};
+use super::error::Validator;
+use super::HumanCfg;
 
-#[derive(Deserialize, Clone)]
+/// How a module's bounds are divided into rooms
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    /// Binary space partitioning, driven by the `divide_*` and `split_*` fields
+    Bsp,
+    /// Squarified treemap over randomly sized target areas
+    Treemap,
+    /// Rooms grown outwards from seed cells on a grid
+    Grid,
+    /// A corridor along the module at `spine_offset`, with a row of rooms either side opening onto it
+    Spine,
+}
+
+/// Which of a module's centre lines its layout is reflected across
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mirror {
+    None,
+    /// Across the line running along the middle of the module, so the port and starboard halves match
+    Lateral,
+    /// Across the line halfway along the module, so the spinward and antispinward halves match
+    Length,
+    /// Across both lines, so all four quarters match
+    Both,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ModuleCfg {
     pub seed: u64,
+    pub layout: LayoutMode,
+    /// Lays out one half or quarter of the module and reflects it into the rest. `room_count` and `divisions` are shared between the parts.
+    pub mirror: Mirror,
     pub room_count: usize,
+    /// Number of splits the layout makes. Each adds a room, and any over `room_count` are removed, so this must be at least `room_count`.
     pub divisions: usize,
     pub extent: [f32; 2],
+    /// Floor to ceiling height of the module, in metres
+    pub height: f32,
+    /// Proportion of the module's bounding box that is interior space, per axis
+    pub inner_space: [f32; 3],
+    /// Width of the frame either side of a doorway, in metres
+    pub door_frame: f32,
+    /// Smallest a room may be across the module (x), in metres
+    pub min_room_width: f32,
+    /// Smallest a room may be along the module (y), in metres
+    pub min_room_depth: f32,
+    /// Largest ratio of a room's long side to its short side
+    pub max_aspect_ratio: f32,
+    /// Share of the doors beyond a spanning tree that are kept. 0 gives a tree with no loops, 1 keeps every door.
+    pub loop_factor: f32,
+    /// Chance of each room being merged into a neighbour, making L, T and U shaped rooms. 0 keeps every room a rectangle.
+    pub merge_rate: f32,
     pub divide_area_chance: f32,
     pub divide_disparity_chance: f32,
     pub split_offset: f32,
     pub split_degredation: f32,
+    /// Distance of the spine's centre line from the middle of the module, in metres. Positive is towards +x. When the
+    /// module is mirrored across its width, it is measured from the middle of the half the spine is laid out in instead.
+    /// Validation keeps the spine inside that width; the layout clamps it there for configs that skip validation.
+    pub spine_offset: f32,
+    /// Width of the spine corridor, in metres
+    pub spine_width: f32,
+}
+
+impl Default for ModuleCfg {
+    fn default() -> Self {
+        Self::new(0, 12)
+    }
 }
 
 impl ModuleCfg {
     pub fn new(seed: u64, room_count: usize) -> Self {
         Self {
             seed,
+            layout: LayoutMode::Bsp,
+            mirror: Mirror::None,
             room_count,
-            divisions: (room_count as f32 / 2.) as usize,
+            // As many splits again are spare, as some are rejected for breaking the size limits. Anything below
+            // `room_count` fails validation and leaves the module short of rooms.
+            divisions: room_count * 2,
             extent: [64., 128.],
+            height: 64.,
+            inner_space: [0.5, 0.5, 1.],
+            door_frame: 0.2,
+            loop_factor: 1.,
+            merge_rate: 0.,
+            min_room_width: 2.,
+            min_room_depth: 2.,
+            max_aspect_ratio: 4.,
             divide_area_chance: 0.55,
             divide_disparity_chance: 0.1,
             split_offset: 0.4,
             split_degredation: 0.9,
+            spine_offset: 0.,
+            spine_width: 3.,
         }
     }
 
     pub fn extent(&self) -> Extent2<f32> {
         Extent2::new(self.extent[0], self.extent[1])
     }
+
+    pub fn inner_space(&self) -> Vec3<f32> {
+        Vec3::new(self.inner_space[0], self.inner_space[1], self.inner_space[2])
+    }
+
+    pub(crate) fn validate(&self, path: &str, v: &mut Validator) {
+        v.at_least(path, "room_count", self.room_count, 1);
+        // Each division adds a room, and the surplus is removed afterwards
+        let reason = format!("must not exceed divisions ({})", self.divisions);
+        v.check(self.room_count <= self.divisions, path, "room_count", &reason);
+        v.positive(path, "extent[0]", self.extent[0]);
+        v.positive(path, "extent[1]", self.extent[1]);
+        v.positive(path, "height", self.height);
+        for (i, space) in self.inner_space.iter().enumerate() {
+            let field = format!("inner_space[{}]", i);
+            let reason = format!("must be between 0 (exclusive) and 1 (found {})", space);
+            v.check(*space > 0. && *space <= 1., path, &field, &reason);
+        }
+        v.unit(path, "divide_area_chance", self.divide_area_chance);
+        v.unit(path, "divide_disparity_chance", self.divide_disparity_chance);
+        // The split point is drawn from [offset, 1 - offset), which is empty from 0.5 upwards
+        let reason = format!("must be between 0 and 0.5 (found {})", self.split_offset);
+        v.check(self.split_offset >= 0. && self.split_offset < 0.5, path, "split_offset", &reason);
+        v.unit(path, "split_degredation", self.split_degredation);
+        v.at_least(path, "door_frame", self.door_frame, 0.);
+        v.unit(path, "loop_factor", self.loop_factor);
+        v.unit(path, "merge_rate", self.merge_rate);
+        v.at_least(path, "min_room_width", self.min_room_width, 0.);
+        v.at_least(path, "min_room_depth", self.min_room_depth, 0.);
+        v.at_least(path, "max_aspect_ratio", self.max_aspect_ratio, 1.);
+        let reason = format!("must not exceed the module's width ({})", self.extent[0]);
+        v.check(self.min_room_width <= self.extent[0], path, "min_room_width", &reason);
+        let reason = format!("must not exceed the module's depth ({})", self.extent[1]);
+        v.check(self.min_room_depth <= self.extent[1], path, "min_room_depth", &reason);
+        if self.layout == LayoutMode::Spine {
+            v.positive(path, "spine_width", self.spine_width);
+            // Mirrored across the module, the spine is laid out in one half and reflected into the other
+            let width = match self.mirror {
+                Mirror::Lateral | Mirror::Both => self.extent[0] / 2.,
+                Mirror::None | Mirror::Length => self.extent[0],
+            };
+            let reason = format!("must keep the spine inside the width it is laid out in ({})", width);
+            let inside = self.spine_offset.abs() + self.spine_width / 2. <= width / 2.;
+            v.check(inside, path, "spine_offset", &reason);
+        }
+    }
+
+    /// A door one human wide plus its frame either side. This is the shortest wall a door fits in, and the width of
+    /// every corridor; the opening itself is only the human's width.
+    pub fn door_clearance(&self, humans: &HumanCfg) -> f32 {
+        humans.width() + 2. * self.door_frame
+    }
+
+    /// True if a room of the given size meets the minimum dimensions and aspect ratio
+    pub fn fits_room(&self, w: f32, h: f32) -> bool {
+        let aspect = w.max(h) / w.min(h).max(f32::EPSILON);
+        w >= self.min_room_width && h >= self.min_room_depth && aspect <= self.max_aspect_ratio
+    }
 }
\ No newline at end of file
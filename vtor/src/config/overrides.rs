@@ -0,0 +1,69 @@
+use toml::value::{Table, Value};
+use super::error::ConfigProblem;
+
+/// A single `path.to.field=value` override, as given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Override {
+    pub path: Vec<String>,
+    pub value: Value,
+}
+
+impl Override {
+    /// Parses `key=value`. The value is read as toml, so `modules.extent=[32, 64]` and `modules.room_count={ min = 4, max = 8 }`
+    /// work as they would in a file. Anything that is not valid toml is taken as a plain string.
+    pub fn parse(text: &str) -> Result<Self, ConfigProblem> {
+        let mut parts = text.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let raw = match parts.next() {
+            Some(raw) => raw.trim(),
+            None => return Err(ConfigProblem::new(text, "expected key=value")),
+        };
+
+        let path: Vec<String> = key.split('.').map(|p| p.trim().to_string()).collect();
+        if path.iter().any(|p| p.is_empty()) {
+            return Err(ConfigProblem::new(text, "expected a dotted key, such as modules.room_count"));
+        }
+
+        let value = match toml::from_str::<Table>(&format!("value = {}", raw)) {
+            Ok(mut table) => table.remove("value").unwrap(),
+            Err(_) => Value::String(raw.to_string()),
+        };
+
+        Ok(Self {
+            path,
+            value,
+        })
+    }
+
+    pub fn key(&self) -> String {
+        self.path.join(".")
+    }
+
+    /// Writes the value into the given config, creating any missing tables along the way
+    pub fn apply(&self, config: &mut Table) -> Result<(), ConfigProblem> {
+        let (field, tables) = self.path.split_last().unwrap();
+        let mut table = config;
+        for (i, name) in tables.iter().enumerate() {
+            let next = table.entry(name.clone()).or_insert_with(|| Value::Table(Table::new()));
+            table = match next {
+                Value::Table(t) => t,
+                _ => return Err(ConfigProblem::new(self.path[..=i].join("."), "is a value, not a table".to_string())),
+            };
+        }
+        table.insert(field.clone(), self.value.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn override_test() {
+    let mut config = Table::new();
+    Override::parse("modules.seed=42").unwrap().apply(&mut config).unwrap();
+    Override::parse("sector.2.modules.room_count = { min = 4, max = 8 }").unwrap().apply(&mut config).unwrap();
+    assert_eq!(config["modules"]["seed"].as_integer(), Some(42));
+    assert!(config["sector"]["2"]["modules"]["room_count"].is_table());
+
+    assert!(Override::parse("modules.seed").is_err());
+    assert!(Override::parse("modules..seed=1").is_err());
+    assert!(Override::parse("modules.seed.x=1").unwrap().apply(&mut config).is_err());
+}
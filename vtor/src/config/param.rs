@@ -0,0 +1,116 @@
+use toml::value::{Table, Value};
+use crate::rng::Pcg32;
+#[cfg(test)]
+use crate::seed::Seed;
+use std::f64::consts::PI;
+
+/// A config value that is drawn per module rather than fixed, e.g. `room_count = { min = 10, max = 20 }`
+/// or `split_offset = { normal = [0.4, 0.05] }`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    /// Uniform over [min, max]
+    Range { min: f64, max: f64 },
+    /// Normal distribution, clamped to [min, max]. Without explicit bounds it is clamped to three standard deviations.
+    Normal { mean: f64, std_dev: f64, min: Option<f64>, max: Option<f64> },
+}
+
+impl Param {
+    /// Reads a param from a toml table, returning a reason if it is malformed
+    pub fn from_table(table: &Table) -> Result<Self, String> {
+        let number = |key: &str| -> Result<Option<f64>, String> {
+            match table.get(key) {
+                None => Ok(None),
+                Some(Value::Integer(i)) => Ok(Some(*i as f64)),
+                Some(Value::Float(f)) => Ok(Some(*f)),
+                Some(_) => Err(format!("`{}` must be a number", key)),
+            }
+        };
+        for key in table.keys() {
+            if !["min", "max", "normal"].contains(&key.as_str()) {
+                return Err(format!("unknown key `{}` (expected `min`, `max` or `normal`)", key));
+            }
+        }
+
+        let min = number("min")?;
+        let max = number("max")?;
+        let param = match table.get("normal") {
+            Some(Value::Array(pair)) if pair.len() == 2 => {
+                let mut nums = pair.iter().filter_map(|v| v.as_float().or(v.as_integer().map(|i| i as f64)));
+                match (nums.next(), nums.next()) {
+                    (Some(mean), Some(std_dev)) => Param::Normal { mean, std_dev, min, max },
+                    _ => return Err("`normal` must be [mean, std_dev]".to_string()),
+                }
+            }
+            Some(_) => return Err("`normal` must be [mean, std_dev]".to_string()),
+            None => match (min, max) {
+                (Some(min), Some(max)) => Param::Range { min, max },
+                _ => return Err("ranges need both `min` and `max`".to_string()),
+            },
+        };
+
+        let (low, high) = param.bounds();
+        if low > high {
+            return Err(format!("min ({}) is greater than max ({})", low, high));
+        }
+        if let Param::Normal { std_dev, .. } = param {
+            if std_dev < 0. {
+                return Err(format!("standard deviation must not be negative (found {})", std_dev));
+            }
+        }
+        Ok(param)
+    }
+
+    /// The smallest and largest values this param can produce
+    pub fn bounds(&self) -> (f64, f64) {
+        match *self {
+            Param::Range { min, max } => (min, max),
+            Param::Normal { mean, std_dev, min, max } => (min.unwrap_or(mean - 3. * std_dev), max.unwrap_or(mean + 3. * std_dev)),
+        }
+    }
+
+    /// The value used where no module is being drawn for, such as the base config
+    pub fn nominal(&self) -> f64 {
+        match *self {
+            Param::Range { min, max } => (min + max) / 2.,
+            Param::Normal { mean, .. } => mean,
+        }
+    }
+
+    pub fn draw(&self, rng: &mut Pcg32) -> f64 {
+        let (low, high) = self.bounds();
+        match *self {
+            Param::Range { min, max } => min + (max - min) * rng.gen_f64(),
+            Param::Normal { mean, std_dev, .. } => {
+                // Box-Muller transform
+                let u1: f64 = 1. - rng.gen_f64();
+                let u2 = rng.gen_f64();
+                let z = (-2. * u1.ln()).sqrt() * (2. * PI * u2).cos();
+                (mean + z * std_dev).max(low).min(high)
+            }
+        }
+    }
+
+    /// Converts a drawn value into a toml value of the same kind as `like`
+    pub fn to_value(x: f64, like: &Value) -> Value {
+        match like {
+            Value::Integer(_) => Value::Integer(x.round() as i64),
+            _ => Value::Float(x),
+        }
+    }
+}
+
+#[test]
+fn param_test() {
+    let table: Table = toml::from_str("normal = [0.4, 0.05]\nmax = 0.45").unwrap();
+    let param = Param::from_table(&table).unwrap();
+    assert_eq!(param.bounds(), (0.4 - 0.15, 0.45));
+
+    let mut rng = Seed::new(1).child("split_offset").rng();
+    for _ in 0..100 {
+        let x = param.draw(&mut rng);
+        assert!((0.25..=0.45).contains(&x));
+    }
+
+    let table: Table = toml::from_str("min = 20\nmax = 10").unwrap();
+    assert!(Param::from_table(&table).is_err());
+}
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use super::error::Validator;
+use crate::generation::{LinkType, ConnectorKind};
+
+/// What each kind of link costs to walk through, as a multiple of the distance walked to reach it
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathCfg {
+    /// A door through a shared wall
+    direct: f32,
+    tunnel: f32,
+    bridge: f32,
+    main: f32,
+    /// Connectors between decks, as a multiple of the height climbed as well as the distance walked to reach them
+    ladder: f32,
+    stairs: f32,
+    lift: f32,
+}
+
+impl Default for PathCfg {
+    fn default() -> Self {
+        Self {
+            direct: 1.,
+            tunnel: 1.,
+            bridge: 1.,
+            main: 1.,
+            ladder: 1.,
+            stairs: 1.,
+            lift: 1.,
+        }
+    }
+}
+
+impl PathCfg {
+    pub fn cost(&self, link_type: LinkType) -> f32 {
+        match link_type {
+            LinkType::Direct(_) => self.direct,
+            LinkType::Tunnel => self.tunnel,
+            LinkType::Bridge => self.bridge,
+            LinkType::Main => self.main,
+        }
+    }
+
+    pub fn connector(&self, kind: ConnectorKind) -> f32 {
+        match kind {
+            ConnectorKind::Ladder => self.ladder,
+            ConnectorKind::Stairs => self.stairs,
+            ConnectorKind::Lift => self.lift,
+        }
+    }
+
+    /// The cost of a plain door, as used for boundary ports between modules
+    pub fn door(&self) -> f32 {
+        self.direct
+    }
+
+    /// The lowest cost of any link on a single deck, which keeps path estimates from overshooting
+    pub fn cheapest(&self) -> f32 {
+        self.direct.min(self.tunnel).min(self.bridge).min(self.main)
+    }
+
+    pub(crate) fn validate(&self, path: &str, v: &mut Validator) {
+        v.positive(path, "direct", self.direct);
+        v.positive(path, "tunnel", self.tunnel);
+        v.positive(path, "bridge", self.bridge);
+        v.positive(path, "main", self.main);
+        v.positive(path, "ladder", self.ladder);
+        v.positive(path, "stairs", self.stairs);
+        v.positive(path, "lift", self.lift);
+    }
+}
@@ -0,0 +1,17 @@
+/// Built-in configs, selectable by name. Each is a complete, current-schema config file.
+const PRESETS: &[(&str, &str)] = &[
+    ("standard", include_str!("../../assets/presets/standard.toml")),
+    ("small_outpost", include_str!("../../assets/presets/small_outpost.toml")),
+    ("large_habitat", include_str!("../../assets/presets/large_habitat.toml")),
+];
+
+/// The names of every built-in preset
+pub fn names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, _)| *name).collect()
+}
+
+/// Finds a preset by name. Case, spaces and hyphens are ignored, so "Small Outpost" finds `small_outpost`.
+pub fn find(name: &str) -> Option<(&'static str, &'static str)> {
+    let key: String = name.trim().to_lowercase().chars().map(|c| if c == ' ' || c == '-' { '_' } else { c }).collect();
+    PRESETS.iter().find(|(n, _)| *n == key).copied()
+}
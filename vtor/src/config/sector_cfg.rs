@@ -1,12 +1,27 @@
 use serde::Deserialize;
+use super::error::Validator;
 
 #[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct SectorCfg {
     modules: usize,
 }
 
+impl Default for SectorCfg {
+    fn default() -> Self {
+        Self {
+            modules: 3,
+        }
+    }
+}
+
 impl SectorCfg {
     pub fn modules(&self) -> usize {
         self.modules
     }
-}
\ No newline at end of file
+
+    pub(crate) fn validate(&self, path: &str, v: &mut Validator) {
+        // Arc::subdivide cannot split a sector into fewer than two modules
+        v.at_least(path, "modules", self.modules, 2);
+    }
+}
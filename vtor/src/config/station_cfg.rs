@@ -1,14 +1,34 @@
 use serde::Deserialize;
 use prima::core::OrdNum;
+use super::error::Validator;
+use crate::seed::Seed;
 
 #[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct StationCfg {
+    /// Root of every seed in the station, see `Seed`
+    seed: u64,
     sectors: usize,
     major: f64,
     minor: f64,
 }
 
+impl Default for StationCfg {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            sectors: 6,
+            major: 800.,
+            minor: 32.,
+        }
+    }
+}
+
 impl StationCfg {
+    pub fn seed(&self) -> Seed {
+        Seed::new(self.seed)
+    }
+
     pub fn sectors(&self) -> usize {
         self.sectors
     }
@@ -20,4 +40,11 @@ impl StationCfg {
     pub fn minor<T>(&self) -> Option<T> where T: OrdNum {
         T::from_f64(self.minor)
     }
-}
\ No newline at end of file
+
+    pub(crate) fn validate(&self, path: &str, v: &mut Validator) {
+        v.at_least(path, "sectors", self.sectors, 1);
+        v.positive(path, "major", self.major);
+        v.positive(path, "minor", self.minor);
+        v.check(self.minor < self.major, path, "minor", "must be smaller than major");
+    }
+}
@@ -0,0 +1,49 @@
+use crate::geom::Compass;
+use vek::Vec2;
+
+/// Where a module sits in the station: its sector, and its place within that sector
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ModuleAddress {
+    pub sector: usize,
+    pub module: usize,
+}
+
+impl ModuleAddress {
+    pub fn new(sector: usize, module: usize) -> Self {
+        Self {
+            sector,
+            module,
+        }
+    }
+
+    /// Position around the ring, counting spinward from the first module of sector 0
+    pub fn ring_index(&self, modules_per_sector: usize) -> usize {
+        self.sector * modules_per_sector + self.module
+    }
+
+    pub fn from_ring_index(index: usize, modules_per_sector: usize) -> Self {
+        Self::new(index / modules_per_sector, index % modules_per_sector)
+    }
+}
+
+/// A door through the end wall of a module, agreed with the neighbouring module so both open onto the same spot.
+/// Modules run spinward along their y axis, so spinward ports lie on the north edge and antispinward ports on the south.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundaryPort {
+    pub edge: Compass,
+    /// Distance of the door's centre from the module's centre line, across the module
+    pub offset: f32,
+    /// Clear width of the doorway
+    pub width: f32,
+    pub neighbour: ModuleAddress,
+}
+
+/// A boundary port placed in a module, opening off the corridor that runs across that end of the module
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Port {
+    pub boundary: BoundaryPort,
+    /// Centre of the doorway, in module space
+    pub position: Vec2<f32>,
+    /// The room the port opens into
+    pub room: usize,
+}
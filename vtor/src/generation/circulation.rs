@@ -0,0 +1,108 @@
+use crate::config::HumanCfg;
+use prima::geom::BoundingRect;
+use vek::{Extent2, Vec2};
+
+/// A way up or down between decks
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ConnectorKind {
+    Ladder,
+    Stairs,
+    /// A shaft stopping at every deck it passes through
+    Lift,
+}
+
+impl ConnectorKind {
+    /// Size in plan, across then along the direction of travel, to climb the given height. Stairs run twice as far as they rise.
+    pub fn footprint(&self, humans: &HumanCfg, rise: f32) -> Extent2<f32> {
+        let width = humans.width();
+        match self {
+            ConnectorKind::Ladder => Extent2::new(width, width / 2.),
+            ConnectorKind::Stairs => Extent2::new(width, 2. * rise),
+            ConnectorKind::Lift => Extent2::new(1.5 * width, 1.5 * width),
+        }
+    }
+
+    /// Floor kept clear on every side of the footprint, on each deck the connector stops at, so people can get on and off
+    pub fn clearance(&self, humans: &HumanCfg) -> f32 {
+        match self {
+            ConnectorKind::Ladder | ConnectorKind::Stairs => humans.width(),
+            // Room to wait for the car as well as to step out of it
+            ConnectorKind::Lift => 1.5 * humans.width(),
+        }
+    }
+
+    /// Fits a connector into a floor area shared by the rooms it joins, centred and running along the longer side.
+    /// None if the footprint and its clearance do not fit.
+    pub(crate) fn fit(&self, area: &BoundingRect<f32>, humans: &HumanCfg, rise: f32) -> Option<BoundingRect<f32>> {
+        let footprint = self.footprint(humans, rise);
+        let clearance = self.clearance(humans);
+        let size = area.size();
+        let along_y = size.h >= size.w;
+        let size_needed = if along_y { Vec2::new(footprint.w, footprint.h) } else { Vec2::new(footprint.h, footprint.w) };
+        if size_needed.x + 2. * clearance > size.w || size_needed.y + 2. * clearance > size.h {
+            return None;
+        }
+        let centre = area.center();
+        Some(BoundingRect::new(centre - size_needed / 2., centre + size_needed / 2.))
+    }
+
+    /// Fits a connector into a room against one of its walls, running along it up to a door in the wall. `wall` is the
+    /// stretch of wall shared with the higher room, which the footprint and its clearance must stay within.
+    pub(crate) fn fit_against(&self, room: &BoundingRect<f32>, wall: &BoundingRect<f32>, humans: &HumanCfg, rise: f32) -> Option<BoundingRect<f32>> {
+        let footprint = self.footprint(humans, rise);
+        let clearance = self.clearance(humans);
+        let along_y = wall.size().w == 0.;
+        let (length, depth) = if along_y { (wall.size().h, room.size().w) } else { (wall.size().w, room.size().h) };
+        if footprint.h + 2. * clearance > length || footprint.w + clearance > depth {
+            return None;
+        }
+
+        let centre = wall.center();
+        let half = footprint.h / 2.;
+        Some(if along_y {
+            // Out from the wall into the room, on whichever side of it the room lies
+            let x = wall.min.x;
+            let (min, max) = if room.max.x <= x { (x - footprint.w, x) } else { (x, x + footprint.w) };
+            BoundingRect::new(Vec2::new(min, centre.y - half), Vec2::new(max, centre.y + half))
+        } else {
+            let y = wall.min.y;
+            let (min, max) = if room.max.y <= y { (y - footprint.w, y) } else { (y, y + footprint.w) };
+            BoundingRect::new(Vec2::new(centre.x - half, min), Vec2::new(centre.x + half, max))
+        })
+    }
+}
+
+/// A connector placed in a module, stopping in a stack of rooms
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connector {
+    pub kind: ConnectorKind,
+    /// Where the connector stands, in plan
+    pub rect: BoundingRect<f32>,
+    /// The rooms it stops in, from the bottom up
+    pub rooms: Vec<usize>,
+}
+
+#[test]
+fn fit_test() {
+    let humans = HumanCfg::default();
+    let area = BoundingRect::new(Vec2::zero(), Vec2::new(12., 6.));
+
+    // Stairs turn to run along the longer side
+    let stairs = ConnectorKind::Stairs.fit(&area, &humans, 3.).unwrap();
+    assert_eq!(stairs.size().w, 6.);
+    assert_eq!(stairs.center(), area.center());
+
+    // A lift needs room to wait either side of the car, which a narrow area does not have
+    assert!(ConnectorKind::Lift.fit(&area, &humans, 3.).is_some());
+    let narrow = BoundingRect::new(Vec2::zero(), Vec2::new(12., 4.));
+    assert!(ConnectorKind::Lift.fit(&narrow, &humans, 3.).is_none());
+    assert!(ConnectorKind::Ladder.fit(&narrow, &humans, 3.).is_some());
+
+    // Against a wall, stairs stand on the room's side of it and stay within the stretch shared with the room above
+    let wall = BoundingRect::new(Vec2::new(12., 1.), Vec2::new(12., 11.));
+    let room = BoundingRect::new(Vec2::new(8., 0.), Vec2::new(12., 12.));
+    let stairs = ConnectorKind::Stairs.fit_against(&room, &wall, &humans, 3.).unwrap();
+    assert_eq!((stairs.min.x, stairs.max.x), (10.8, 12.));
+    assert_eq!((stairs.min.y, stairs.max.y), (3., 9.));
+    assert!(ConnectorKind::Stairs.fit_against(&room, &wall, &humans, 4.).is_none());
+}
@@ -0,0 +1,234 @@
+use prima::geom::{BoundingRect, Line};
+use vek::Vec2;
+use crate::geom::Compass;
+use super::module_factory::shared_edge;
+
+/// Added to the cost of crossing an ordinary room, so routes only cut a corridor through a room when there is no other way
+const THROUGH_ROOM_COST: f32 = 1_000_000.;
+
+/// Something a corridor can be routed through
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Node {
+    /// An existing room, by index. The bool is true if the room may be walked through freely (i.e. it is a corridor),
+    /// otherwise a corridor is cut through it
+    Room(usize, bool),
+    /// A room that can be routed to or from, but not through, such as a compound room
+    Solid(usize),
+    /// A free rectangle, by index into the module's free space
+    Free(usize),
+}
+
+/// Where a route crosses from one node into the next
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Door {
+    pub position: Vec2<f32>,
+    /// Which edge of the node being left the door lies on
+    pub edge: Compass,
+}
+
+/// Finds the cheapest chain of nodes from `start` to `end`, where every step crosses a wall at least `width` long.
+/// Returns the nodes in order, including both ends, along with the door between each consecutive pair.
+pub(crate) fn route(nodes: &[(Node, BoundingRect<f32>)], start: usize, end: usize, width: f32) -> Option<(Vec<Node>, Vec<Door>)> {
+    let n = nodes.len();
+    let mut cost = vec![f32::MAX; n];
+    let mut previous: Vec<Option<usize>> = vec![None; n];
+    let mut done = vec![false; n];
+    cost[start] = 0.;
+
+    // Plain Dijkstra. Modules rarely have more than a few dozen nodes, so a linear scan for the next node is fine
+    loop {
+        let mut current = None;
+        let mut cheapest = f32::MAX;
+        for i in 0..n {
+            if !done[i] && cost[i] < cheapest {
+                cheapest = cost[i];
+                current = Some(i);
+            }
+        }
+        let current = current?;
+        if current == end {
+            break;
+        }
+        done[current] = true;
+
+        for next in 0..n {
+            if done[next] || next == current {
+                continue;
+            }
+            if let Node::Solid(_) = nodes[next].0 {
+                if next != end {
+                    continue;
+                }
+            }
+            match shared_edge(&nodes[current].1, &nodes[next].1) {
+                Some((line, _)) if line.start.distance(line.end) >= width => {}
+                _ => continue,
+            }
+            let mut step = nodes[current].1.center().distance(nodes[next].1.center());
+            if let Node::Room(_, false) = nodes[next].0 {
+                if next != end {
+                    step += THROUGH_ROOM_COST;
+                }
+            }
+            if cost[current] + step < cost[next] {
+                cost[next] = cost[current] + step;
+                previous[next] = Some(current);
+            }
+        }
+    }
+
+    let mut chain = vec![end];
+    while let Some(p) = previous[*chain.last().unwrap()] {
+        chain.push(p);
+    }
+    chain.reverse();
+
+    // Place each door as close as possible to the one before it, so corridors run straight where they can
+    let mut doors = Vec::new();
+    let mut last = nodes[start].1.center();
+    for pair in chain.windows(2) {
+        let (line, edge) = shared_edge(&nodes[pair[0]].1, &nodes[pair[1]].1).unwrap();
+        let position = closest_on_wall(&line, last, width / 2.);
+        doors.push(Door { position, edge });
+        last = position;
+    }
+
+    Some((chain.iter().map(|i| nodes[*i].0).collect(), doors))
+}
+
+/// The point on the wall nearest to `target`, keeping `margin` clear of either end
+fn closest_on_wall(wall: &Line<f32>, target: Vec2<f32>, margin: f32) -> Vec2<f32> {
+    let clamp = |min: f32, max: f32, v: f32| {
+        if max - min <= 2. * margin {
+            (min + max) / 2.
+        } else {
+            v.max(min + margin).min(max - margin)
+        }
+    };
+    if wall.start.x == wall.end.x {
+        Vec2::new(wall.start.x, clamp(wall.start.y.min(wall.end.y), wall.start.y.max(wall.end.y), target.y))
+    } else {
+        Vec2::new(clamp(wall.start.x.min(wall.end.x), wall.start.x.max(wall.end.x), target.x), wall.start.y)
+    }
+}
+
+/// Lays out a corridor of the given width across a free rectangle, from a door on one of its walls to a door on another.
+/// Returns the corridor as a chain of non-overlapping rectangles, and the open joins between consecutive rectangles.
+pub(crate) fn carve(space: &BoundingRect<f32>, entry: Door, exit: Door, width: f32) -> (Vec<BoundingRect<f32>>, Vec<Door>) {
+    let half = width / 2.;
+    let (p, q) = (entry.position, exit.position);
+    let vertical = |edge: Compass| edge == Compass::East || edge == Compass::West;
+    let lane = |min: f32, max: f32, v: f32| if max - min <= width { (min + max) / 2. } else { v.max(min + half).min(max - half) };
+
+    // Entry and exit edges are given relative to this space
+    let points = if vertical(entry.edge) && vertical(exit.edge) {
+        if (p.y - q.y).abs() < 1e-3 {
+            vec![p, q]
+        } else {
+            let inward = if entry.edge == Compass::West { width } else { -width };
+            let x = if entry.edge == exit.edge { p.x + inward } else { (p.x + q.x) / 2. };
+            let x = lane(space.min.x, space.max.x, x);
+            vec![p, Vec2::new(x, p.y), Vec2::new(x, q.y), q]
+        }
+    } else if !vertical(entry.edge) && !vertical(exit.edge) {
+        if (p.x - q.x).abs() < 1e-3 {
+            vec![p, q]
+        } else {
+            let inward = if entry.edge == Compass::South { width } else { -width };
+            let y = if entry.edge == exit.edge { p.y + inward } else { (p.y + q.y) / 2. };
+            let y = lane(space.min.y, space.max.y, y);
+            vec![p, Vec2::new(p.x, y), Vec2::new(q.x, y), q]
+        }
+    } else if vertical(entry.edge) {
+        vec![p, Vec2::new(q.x, p.y), q]
+    } else {
+        vec![p, Vec2::new(p.x, q.y), q]
+    };
+
+    let mut points: Vec<Vec2<f32>> = points;
+    points.dedup_by(|a, b| a.distance(*b) < 1e-4);
+
+    let segments = points.len() - 1;
+    let mut pieces = Vec::new();
+    let mut joins = Vec::new();
+    for i in 0..segments {
+        let (a, b) = (points[i], points[i + 1]);
+        let dir = (b - a) / a.distance(b).max(1e-6);
+        // Each corner square belongs to the segment arriving at it
+        let start = if i > 0 { a + dir * half } else { a };
+        let end = if i + 1 < segments { b + dir * half } else { b };
+        if (end - start).dot(dir) <= 1e-4 {
+            continue;
+        }
+
+        let min = Vec2::new(start.x.min(end.x), start.y.min(end.y));
+        let max = Vec2::new(start.x.max(end.x), start.y.max(end.y));
+        let rect = if dir.x.abs() > dir.y.abs() {
+            BoundingRect::new(Vec2::new(min.x, min.y - half), Vec2::new(max.x, max.y + half))
+        } else {
+            BoundingRect::new(Vec2::new(min.x - half, min.y), Vec2::new(max.x + half, max.y))
+        };
+
+        if !pieces.is_empty() {
+            let edge = if dir.x > 0.5 {
+                Compass::West
+            } else if dir.x < -0.5 {
+                Compass::East
+            } else if dir.y > 0.5 {
+                Compass::South
+            } else {
+                Compass::North
+            };
+            // The join lies on the previous piece's far edge, i.e. the opposite of this piece's near edge
+            joins.push(Door { position: start, edge: edge.opposite() });
+        }
+        pieces.push(rect);
+    }
+    (pieces, joins)
+}
+
+/// The parts of `rect` not covered by `cut`, as up to four rectangles
+pub(crate) fn subtract(rect: &BoundingRect<f32>, cut: &BoundingRect<f32>) -> Vec<BoundingRect<f32>> {
+    let overlaps = cut.min.x < rect.max.x && cut.max.x > rect.min.x && cut.min.y < rect.max.y && cut.max.y > rect.min.y;
+    if !overlaps {
+        return vec![*rect];
+    }
+
+    let mut parts = Vec::new();
+    let y_min = cut.min.y.max(rect.min.y);
+    let y_max = cut.max.y.min(rect.max.y);
+    if cut.min.y > rect.min.y {
+        parts.push(BoundingRect::new(rect.min, Vec2::new(rect.max.x, cut.min.y)));
+    }
+    if cut.max.y < rect.max.y {
+        parts.push(BoundingRect::new(Vec2::new(rect.min.x, cut.max.y), rect.max));
+    }
+    if cut.min.x > rect.min.x {
+        parts.push(BoundingRect::new(Vec2::new(rect.min.x, y_min), Vec2::new(cut.min.x, y_max)));
+    }
+    if cut.max.x < rect.max.x {
+        parts.push(BoundingRect::new(Vec2::new(cut.max.x, y_min), Vec2::new(rect.max.x, y_max)));
+    }
+    parts
+}
+
+#[test]
+fn carve_test() {
+    let space = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(10., 10.));
+    let entry = Door { position: Vec2::new(0., 2.), edge: Compass::West };
+    let exit = Door { position: Vec2::new(10., 8.), edge: Compass::East };
+    let (pieces, joins) = carve(&space, entry, exit, 2.);
+
+    // A Z-shaped corridor, with each piece touching the next
+    assert_eq!(pieces.len(), 3);
+    assert_eq!(joins.len(), 2);
+    for pair in pieces.windows(2) {
+        assert!(shared_edge(&pair[0], &pair[1]).is_some());
+    }
+    let area: f32 = pieces.iter().map(|p| p.size().w * p.size().h).sum();
+    assert!((area - (5. * 2. + 6. * 2. + 5. * 2.)).abs() < 1e-3);
+
+    let rest = subtract(&space, &pieces[1]);
+    let rest_area: f32 = rest.iter().map(|p| p.size().w * p.size().h).sum();
+    assert!((rest_area - (100. - 12.)).abs() < 1e-3);
+}
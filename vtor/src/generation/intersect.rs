@@ -1,12 +1,36 @@
 use crate::geom::Compass;
+use prima::geom::Line;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The wall segment shared by two directly adjacent rooms
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Intersect {
+    pub line: Line<f32>,
+    /// The rooms either side of the wall, lowest index first
+    pub rooms: (usize, usize),
+    /// Which edge of the first room the wall lies on
     pub edge: Compass,
+    /// The door placed in this wall, if one fits
+    pub portal: Option<usize>,
 }
 
+impl Intersect {
+    pub fn length(&self) -> f32 {
+        self.line.start.distance(self.line.end)
+    }
+
+    /// The room on the other side of the wall from the given one
+    pub fn other(&self, room: usize) -> usize {
+        if room == self.rooms.0 {
+            self.rooms.1
+        } else {
+            self.rooms.0
+        }
+    }
+}
+
+/// A room's reference to an intersect, along with which of its own edges the intersect lies on
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct IntersectRef {
     pub index: usize,
     pub edge: Compass,
-}
\ No newline at end of file
+}
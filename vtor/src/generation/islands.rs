@@ -0,0 +1,78 @@
+use super::Room;
+
+/// Disjoint-set forest over room indices, with path halving and union by size.
+pub(crate) struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSet {
+    pub fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Joins the sets holding `a` and `b`, returning false if they were already joined
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
+
+/// Groups rooms into islands of linked rooms. Islands are ordered by their lowest room index, and each lists its rooms in ascending order.
+pub(crate) fn collect_islands(rooms: &[Room]) -> Vec<Vec<usize>> {
+    let mut set = DisjointSet::new(rooms.len());
+    for (i, room) in rooms.iter().enumerate() {
+        for j in room.connected() {
+            set.union(i, j);
+        }
+    }
+
+    // Rooms are visited in order, so each island is created by its lowest room
+    let mut island_of = vec![usize::MAX; rooms.len()];
+    let mut islands: Vec<Vec<usize>> = Vec::new();
+    for i in 0..rooms.len() {
+        let root = set.find(i);
+        if island_of[root] == usize::MAX {
+            island_of[root] = islands.len();
+            islands.push(Vec::new());
+        }
+        islands[island_of[root]].push(i);
+    }
+    islands
+}
+
+#[test]
+fn disjoint_set_test() {
+    let mut set = DisjointSet::new(6);
+    assert!(set.union(4, 5));
+    assert!(set.union(0, 4));
+    assert!(!set.union(5, 0));
+    assert_eq!(set.find(5), set.find(0));
+    assert_ne!(set.find(1), set.find(0));
+
+    // A long chain should not need any recursion to resolve
+    let mut set = DisjointSet::new(100_000);
+    for i in 1..100_000 {
+        set.union(i - 1, i);
+    }
+    assert_eq!(set.find(0), set.find(99_999));
+}
@@ -0,0 +1,146 @@
+use crate::config::ModuleCfg;
+use super::{Layout, LayoutStrategy};
+use crate::generation::RoomKind;
+use crate::generation::trace::{Step, Pick, Axis};
+use prima::geom::BoundingRect;
+use prima::core::maths::*;
+use crate::rng::Pcg32;
+
+/// How many split points are tried before a room is left whole
+const MAX_SPLIT_ATTEMPTS: usize = 8;
+
+/// Binary space partitioning. Repeatedly splits a room in two, favouring the largest or most stretched room,
+/// then removes rooms at random until `room_count` remain. Splits that would leave a room smaller than
+/// `min_room_width` by `min_room_depth`, or more stretched than `max_aspect_ratio`, are rejected and redrawn.
+pub struct Bsp;
+
+impl LayoutStrategy for Bsp {
+    fn layout(&self, bounds: &BoundingRect<f32>, config: &ModuleCfg, rng: &mut Pcg32) -> Layout {
+        let mut rooms = vec![*bounds];
+        // Rooms that could not be split without breaking the size limits are never picked again
+        let mut splittable = vec![true];
+        let mut rejected = 0;
+        let mut steps = vec![Step::Place { rect: *bounds, kind: RoomKind::Room }];
+
+        let mut offset = clamp01(config.split_offset);
+        let degredation = clamp01(config.split_degredation);
+
+        for _ in 0..config.divisions {
+            let open: Vec<usize> = (0..rooms.len()).filter(|i| splittable[*i]).collect();
+            if open.is_empty() {
+                break;
+            }
+
+            let mut index = open[0];
+            let pick;
+
+            let v = rng.gen_f32();
+
+            if v > config.divide_area_chance {
+                pick = Pick::Area;
+                let mut largest_area = 0.;
+                for i in open.iter().copied() {
+                    let area = rooms[i].size().w * rooms[i].size().h;
+                    if area > largest_area {
+                        largest_area = area;
+                        index = i;
+                    }
+                }
+            } else if v > config.divide_disparity_chance {
+                pick = Pick::Disparity;
+                let mut largest_disparity = 0.;
+                for i in open.iter().copied() {
+                    let disp = (rooms[i].size().w - rooms[i].size().h).abs();
+                    if disp > largest_disparity {
+                        largest_disparity = disp;
+                        index = i;
+                    }
+                }
+            } else {
+                pick = Pick::Random;
+                index = open[rng.gen_range(0, open.len())];
+            }
+
+            let random_room = rooms[index];
+            let extent = random_room.size();
+
+            // Redraw the split point until both halves are big enough and not too stretched
+            let axis = if extent.w > extent.h { Axis::X } else { Axis::Y };
+            let mut split = None;
+            for _ in 0..MAX_SPLIT_ATTEMPTS {
+                let r = rng.gen_range(offset, 1. - offset);
+                let (at, halves) = match axis {
+                    Axis::X => (random_room.min.x + r * extent.w, random_room.split_at_x(random_room.min.x + r * extent.w)),
+                    Axis::Y => (random_room.min.y + r * extent.h, random_room.split_at_y(random_room.min.y + r * extent.h)),
+                };
+                if halves.iter().all(|h| config.fits_room(h.size().w, h.size().h)) {
+                    split = Some((at, halves));
+                    break;
+                }
+                steps.push(Step::Reject { room: index, axis, at });
+                rejected += 1;
+            }
+            // Degrade offset so it becomes less centered
+            offset *= degredation;
+
+            let (at, split) = match split {
+                Some(split) => split,
+                None => {
+                    steps.push(Step::Exhausted { room: index });
+                    splittable[index] = false;
+                    continue;
+                }
+            };
+
+            rooms.remove(index);
+            splittable.remove(index);
+            let halves = match axis {
+                Axis::X => [split[0].made_valid(), split[1].made_valid()],
+                Axis::Y => [split[1].made_valid(), split[0].made_valid()],
+            };
+            rooms.extend(halves.iter());
+            splittable.push(true);
+            splittable.push(true);
+            steps.push(Step::Split { room: index, pick, axis, at, halves });
+        }
+
+        // Remove the surplus rooms at random until `room_count` remain, keeping the space they leave behind
+        let mut free = Vec::new();
+        for _ in 0..rooms.len().saturating_sub(config.room_count) {
+            let index = rng.gen_range(0, rooms.len());
+            free.push(rooms.remove(index));
+            steps.push(Step::Remove { room: index });
+        }
+
+        Layout { rooms, free, corridors: Vec::new(), rejected, steps }
+    }
+}
+
+#[test]
+fn bsp_limits_test() {
+    use vek::Vec2;
+    let config = ModuleCfg {
+        seed: 73563,
+        room_count: 40,
+        divisions: 60,
+        split_offset: 0.,
+        min_room_width: 3.,
+        min_room_depth: 4.,
+        max_aspect_ratio: 2.5,
+        ..ModuleCfg::default()
+    };
+    let bounds = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(32., 64.));
+    let layout = Bsp.layout(&bounds, &config, &mut Pcg32::from_seed(config.seed));
+
+    assert!(layout.rejected > 0);
+    for room in layout.rooms.iter().chain(layout.free.iter()) {
+        assert!(config.fits_room(room.size().w, room.size().h), "{:?} breaks the limits", room);
+    }
+    assert!(layout.rooms.len() <= config.room_count);
+
+    // With room to split into, exactly `room_count` rooms are left
+    let config = ModuleCfg { seed: 1213, room_count: 12, divisions: 24, ..ModuleCfg::default() };
+    let layout = Bsp.layout(&bounds, &config, &mut Pcg32::from_seed(config.seed));
+    assert_eq!(layout.rooms.len(), config.room_count);
+    assert_eq!(layout.rooms.len() + layout.free.len(), config.divisions + 1);
+}
@@ -0,0 +1,119 @@
+use crate::config::ModuleCfg;
+use super::{Layout, LayoutStrategy};
+use prima::geom::BoundingRect;
+use crate::rng::Pcg32;
+use vek::Vec2;
+
+/// Grid-based room growth. Scatters `room_count` seed cells over a grid, then grows each room one row or column
+/// at a time until none can grow any further or would pass `max_aspect_ratio`. Rooms left smaller than the minimum size
+/// are rejected, and they and any cells no room reached are left as free space.
+pub struct GridGrowth;
+
+impl LayoutStrategy for GridGrowth {
+    fn layout(&self, bounds: &BoundingRect<f32>, config: &ModuleCfg, rng: &mut Pcg32) -> Layout {
+        let size = bounds.size();
+        // Aim for a few cells per room, so each has space to grow into
+        let cell = (size.w * size.h / (config.room_count.max(1) * 4) as f32).sqrt();
+        let cols = ((size.w / cell).round() as usize).max(1);
+        let rows = ((size.h / cell).round() as usize).max(1);
+        let count = config.room_count.max(1).min(cols * rows);
+
+        // Cell edges are computed once so neighbouring rooms share exactly the same coordinates
+        let xs: Vec<f32> = (0..=cols).map(|i| if i == cols { bounds.max.x } else { bounds.min.x + size.w * i as f32 / cols as f32 }).collect();
+        let ys: Vec<f32> = (0..=rows).map(|i| if i == rows { bounds.max.y } else { bounds.min.y + size.h * i as f32 / rows as f32 }).collect();
+
+        let mut grid = Grid { cols, rows, ratio: config.max_aspect_ratio.max(1.), owner: vec![None; cols * rows] };
+        let mut cells: Vec<usize> = (0..cols * rows).collect();
+        rng.shuffle(&mut cells);
+        let mut rooms: Vec<[usize; 4]> = Vec::new();
+        for (room, cell) in cells[..count].iter().enumerate() {
+            grid.owner[*cell] = Some(room);
+            let (x, y) = (cell % cols, cell / cols);
+            rooms.push([x, y, x + 1, y + 1]);
+        }
+
+        let mut growing = true;
+        while growing {
+            growing = false;
+            for (room, extent) in rooms.iter_mut().enumerate() {
+                let mut sides = [0, 1, 2, 3];
+                rng.shuffle(&mut sides);
+                if sides.iter().any(|side| grid.grow(room, extent, *side)) {
+                    growing = true;
+                }
+            }
+        }
+
+        let to_rect = |[x0, y0, x1, y1]: [usize; 4]| BoundingRect::new(Vec2::new(xs[x0], ys[y0]), Vec2::new(xs[x1], ys[y1]));
+
+        let mut rejected = 0;
+        rooms.retain(|extent| {
+            let rect = to_rect(*extent);
+            if config.fits_room(rect.size().w, rect.size().h) {
+                return true;
+            }
+            for y in extent[1]..extent[3] {
+                for x in extent[0]..extent[2] {
+                    grid.owner[y * cols + x] = None;
+                }
+            }
+            rejected += 1;
+            false
+        });
+
+        // Unclaimed cells become free space, merged into runs along each row
+        let mut free = Vec::new();
+        for y in 0..rows {
+            let mut x = 0;
+            while x < cols {
+                if grid.owner[y * cols + x].is_some() {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < cols && grid.owner[y * cols + x].is_none() {
+                    x += 1;
+                }
+                free.push(to_rect([start, y, x, y + 1]));
+            }
+        }
+
+        Layout::whole(rooms.into_iter().map(to_rect).collect(), free, rejected)
+    }
+}
+
+struct Grid {
+    cols: usize,
+    rows: usize,
+    /// Longest side a growing room may reach, as a multiple of its shortest
+    ratio: f32,
+    owner: Vec<Option<usize>>,
+}
+
+impl Grid {
+    /// Tries to grow a room by one row or column on the given side (west, east, south, north), claiming the cells if they are all free
+    fn grow(&mut self, room: usize, extent: &mut [usize; 4], side: usize) -> bool {
+        let [x0, y0, x1, y1] = *extent;
+        let (w, h) = (x1 - x0, y1 - y0);
+        let (wider, taller) = ((w + 1) as f32 <= h as f32 * self.ratio, (h + 1) as f32 <= w as f32 * self.ratio);
+        let (cells, grown) = match side {
+            0 if x0 > 0 && wider => ((x0 - 1..x0, y0..y1), [x0 - 1, y0, x1, y1]),
+            1 if x1 < self.cols && wider => ((x1..x1 + 1, y0..y1), [x0, y0, x1 + 1, y1]),
+            2 if y0 > 0 && taller => ((x0..x1, y0 - 1..y0), [x0, y0 - 1, x1, y1]),
+            3 if y1 < self.rows && taller => ((x0..x1, y1..y1 + 1), [x0, y0, x1, y1 + 1]),
+            _ => return false,
+        };
+
+        let (xs, ys) = cells;
+        let cols = self.cols;
+        let indices: Vec<usize> = ys.flat_map(|y| xs.clone().map(move |x| y * cols + x)).collect();
+        if indices.iter().any(|i| self.owner[*i].is_some()) {
+            return false;
+        }
+        for i in indices {
+            self.owner[i] = Some(room);
+        }
+        *extent = grown;
+        true
+    }
+}
@@ -0,0 +1,52 @@
+mod bsp;
+mod treemap;
+mod grid;
+mod spine;
+
+pub use bsp::Bsp;
+pub use treemap::Treemap;
+pub use grid::GridGrowth;
+pub use spine::Spine;
+
+use crate::config::{LayoutMode, ModuleCfg};
+use prima::geom::BoundingRect;
+use crate::rng::Pcg32;
+use crate::generation::RoomKind;
+use crate::generation::trace::Step;
+
+/// The rooms a strategy divided the module into, along with any space it left empty
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    pub rooms: Vec<BoundingRect<f32>>,
+    pub free: Vec<BoundingRect<f32>>,
+    /// Strips the strategy reserved for corridors, which become corridor rooms after all of `rooms`
+    pub corridors: Vec<BoundingRect<f32>>,
+    /// Candidate rooms thrown away for breaking the size limits in `ModuleCfg`
+    pub rejected: usize,
+    /// How the strategy arrived at the rooms, see `Trace`
+    pub steps: Vec<Step>,
+}
+
+impl Layout {
+    /// For strategies that place every room whole, rather than reaching them through a series of splits
+    pub fn whole(rooms: Vec<BoundingRect<f32>>, free: Vec<BoundingRect<f32>>, rejected: usize) -> Self {
+        let mut steps: Vec<Step> = rooms.iter().map(|rect| Step::Place { rect: *rect, kind: RoomKind::Room }).collect();
+        steps.extend(free.iter().map(|rect| Step::Free { rect: *rect }));
+        Self { rooms, free, corridors: Vec::new(), rejected, steps }
+    }
+}
+
+/// Divides a module's bounds into rooms. Rooms must not overlap, and should meet exactly along shared walls.
+pub trait LayoutStrategy {
+    fn layout(&self, bounds: &BoundingRect<f32>, config: &ModuleCfg, rng: &mut Pcg32) -> Layout;
+}
+
+/// The built-in strategy for the given mode
+pub fn strategy(mode: LayoutMode) -> Box<dyn LayoutStrategy> {
+    match mode {
+        LayoutMode::Bsp => Box::new(Bsp),
+        LayoutMode::Treemap => Box::new(Treemap),
+        LayoutMode::Grid => Box::new(GridGrowth),
+        LayoutMode::Spine => Box::new(Spine),
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::ModuleCfg;
+use super::{Layout, LayoutStrategy};
+use crate::generation::RoomKind;
+use crate::generation::trace::Step;
+use prima::geom::BoundingRect;
+use prima::core::maths::*;
+use crate::rng::Pcg32;
+use vek::Vec2;
+
+/// A corridor running the length of the module, `spine_width` wide and `spine_offset` from the centre line, with a single
+/// row of rooms either side of it. Every room opens onto the spine. `room_count` is shared between the sides by width,
+/// and a side too narrow for `min_room_width` is left as free space. Rooms breaking the other size limits are rejected and
+/// left as free space too.
+pub struct Spine;
+
+impl LayoutStrategy for Spine {
+    fn layout(&self, bounds: &BoundingRect<f32>, config: &ModuleCfg, rng: &mut Pcg32) -> Layout {
+        // Kept inside the bounds, which are only part of the module's width when the layout is mirrored across it
+        let half = (config.spine_width / 2.).min(bounds.size().w / 2.);
+        let centre = ((bounds.min.x + bounds.max.x) / 2. + config.spine_offset).max(bounds.min.x + half).min(bounds.max.x - half);
+        let spine = BoundingRect::new(
+            Vec2::new((centre - half).max(bounds.min.x), bounds.min.y),
+            Vec2::new((centre + half).min(bounds.max.x), bounds.max.y),
+        );
+
+        let sides = [
+            BoundingRect::new(bounds.min, Vec2::new(spine.min.x, bounds.max.y)),
+            BoundingRect::new(Vec2::new(spine.max.x, bounds.min.y), bounds.max),
+        ];
+        let usable: Vec<bool> = sides.iter().map(|s| s.size().w > 0. && s.size().w >= config.min_room_width).collect();
+        let total: f32 = sides.iter().zip(usable.iter()).filter(|(_, u)| **u).map(|(s, _)| s.size().w).sum();
+
+        let mut rooms = Vec::new();
+        let mut free = Vec::new();
+        let mut rejected = 0;
+        for (side, usable) in sides.iter().zip(usable) {
+            if !usable {
+                if side.size().w > 0. {
+                    free.push(*side);
+                }
+                continue;
+            }
+            let share = (config.room_count.max(1) as f32 * side.size().w / total).round() as usize;
+            for room in row(side, share.max(1), config, rng) {
+                if config.fits_room(room.size().w, room.size().h) {
+                    rooms.push(room);
+                } else {
+                    free.push(room);
+                    rejected += 1;
+                }
+            }
+        }
+
+        let mut layout = Layout::whole(rooms, free, rejected);
+        layout.steps.push(Step::Place { rect: spine, kind: RoomKind::Corridor });
+        layout.corridors.push(spine);
+        layout
+    }
+}
+
+/// Cuts a strip into up to `count` rooms along the module, each at least `min_room_depth` deep
+fn row(strip: &BoundingRect<f32>, count: usize, config: &ModuleCfg, rng: &mut Pcg32) -> Vec<BoundingRect<f32>> {
+    let length = strip.size().h;
+    let min_depth = config.min_room_depth.max(f32::EPSILON);
+    let count = count.min((length / min_depth).floor() as usize).max(1);
+
+    // As in the treemap, split offset bounds how much room depths vary
+    let spread = 1. - clamp01(2. * config.split_offset);
+    let weights: Vec<f32> = (0..count).map(|_| 1. + spread * rng.gen_range(-0.9, 0.9)).collect();
+    let total: f32 = weights.iter().sum();
+    let spare = (length - count as f32 * min_depth).max(0.);
+
+    let mut rooms = Vec::new();
+    let mut y = strip.min.y;
+    for (i, weight) in weights.iter().enumerate() {
+        // The last room takes whatever is left, to absorb rounding
+        let next = if i + 1 == count { strip.max.y } else { y + min_depth + spare * weight / total };
+        rooms.push(BoundingRect::new(Vec2::new(strip.min.x, y), Vec2::new(strip.max.x, next)));
+        y = next;
+    }
+    rooms
+}
+
+#[test]
+fn spine_test() {
+    let bounds = BoundingRect::new(Vec2::zero(), Vec2::new(40., 100.));
+    let config = ModuleCfg {
+        layout: crate::config::LayoutMode::Spine,
+        spine_offset: 6.,
+        spine_width: 4.,
+        room_count: 10,
+        ..ModuleCfg::default()
+    };
+    let layout = Spine.layout(&bounds, &config, &mut Pcg32::from_seed(73563));
+
+    let spine = layout.corridors[0];
+    assert_eq!((spine.min.x, spine.max.x), (24., 28.));
+    assert_eq!((spine.min.y, spine.max.y), (0., 100.));
+    // The wider west side gets more of the rooms
+    let west = layout.rooms.iter().filter(|r| r.max.x == spine.min.x).count();
+    let east = layout.rooms.iter().filter(|r| r.min.x == spine.max.x).count();
+    assert_eq!(west + east, layout.rooms.len());
+    assert!(west > east && east > 0);
+    for room in layout.rooms.iter() {
+        assert!(room.size().h >= config.min_room_depth - 1e-4);
+    }
+
+    // Rooms too stretched for the aspect ratio are left free
+    let strict = ModuleCfg { max_aspect_ratio: 1.5, ..config.clone() };
+    let layout = Spine.layout(&bounds, &strict, &mut Pcg32::from_seed(73563));
+    assert!(layout.rejected > 0);
+    assert_eq!(layout.free.len(), layout.rejected);
+    assert!(layout.rooms.iter().all(|r| strict.fits_room(r.size().w, r.size().h)));
+
+    // An offset past the edge of the bounds still leaves the spine inside them
+    let half = BoundingRect::new(Vec2::zero(), Vec2::new(16., 100.));
+    let layout = Spine.layout(&half, &ModuleCfg { spine_offset: 10., ..config.clone() }, &mut Pcg32::from_seed(73563));
+    assert_eq!((layout.corridors[0].min.x, layout.corridors[0].max.x), (12., 16.));
+    assert!(layout.rooms.iter().chain(layout.free.iter()).all(|r| r.min.x >= 0. && r.max.x <= 12.));
+
+    // A side too narrow for a room is left free
+    let config = ModuleCfg { spine_offset: 17., ..config };
+    let layout = Spine.layout(&bounds, &config, &mut Pcg32::from_seed(73563));
+    assert_eq!(layout.free.len(), 1 + layout.rejected);
+    assert!(layout.free.iter().any(|f| f.min.x == layout.corridors[0].max.x));
+    assert!(layout.rooms.iter().all(|r| r.max.x <= layout.corridors[0].min.x));
+}
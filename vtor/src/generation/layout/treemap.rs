@@ -0,0 +1,110 @@
+use crate::config::ModuleCfg;
+use super::{Layout, LayoutStrategy};
+use prima::geom::BoundingRect;
+use prima::core::maths::*;
+use crate::rng::Pcg32;
+use vek::Vec2;
+
+/// Squarified treemap. Draws a target area for each of `room_count` rooms, then packs them in rows
+/// chosen to keep every room as close to square as possible. Rooms that still break the size limits in the config are
+/// rejected and left as free space.
+pub struct Treemap;
+
+impl LayoutStrategy for Treemap {
+    fn layout(&self, bounds: &BoundingRect<f32>, config: &ModuleCfg, rng: &mut Pcg32) -> Layout {
+        let count = config.room_count.max(1);
+        // Split offset keeps BSP rooms near even, so use it here to bound how much room sizes vary
+        let spread = 1. - clamp01(2. * config.split_offset);
+        let mut weights: Vec<f32> = (0..count).map(|_| 1. + spread * rng.gen_range(-0.9, 0.9)).collect();
+        weights.sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+        let size = bounds.size();
+        let total: f32 = weights.iter().sum();
+        let areas: Vec<f32> = weights.iter().map(|w| w / total * size.w * size.h).collect();
+
+        let (rooms, free): (Vec<_>, Vec<_>) = squarify(&areas, *bounds).into_iter()
+            .partition(|r| config.fits_room(r.size().w, r.size().h));
+        let rejected = free.len();
+        Layout::whole(rooms, free, rejected)
+    }
+}
+
+/// Lays out the given areas, largest first, so they exactly tile `rect`
+pub(crate) fn squarify(areas: &[f32], mut rect: BoundingRect<f32>) -> Vec<BoundingRect<f32>> {
+    let mut rooms = Vec::new();
+    let mut i = 0;
+    while i < areas.len() {
+        let side = rect.size().w.min(rect.size().h);
+
+        // Grow the row for as long as doing so improves its worst aspect ratio
+        let mut end = i + 1;
+        while end < areas.len() && worst(&areas[i..end + 1], side) <= worst(&areas[i..end], side) {
+            end += 1;
+        }
+        let row = &areas[i..end];
+        let last_row = end == areas.len();
+        let sum: f32 = row.iter().sum();
+
+        // Rows run along the shorter side. The last row takes whatever space is left, to absorb rounding.
+        if rect.size().w >= rect.size().h {
+            let x = if last_row { rect.max.x } else { rect.min.x + sum / side };
+            let mut y = rect.min.y;
+            for (j, area) in row.iter().enumerate() {
+                let next = if j + 1 == row.len() { rect.max.y } else { y + area / (x - rect.min.x) };
+                rooms.push(BoundingRect::new(Vec2::new(rect.min.x, y), Vec2::new(x, next)));
+                y = next;
+            }
+            rect.min.x = x;
+        } else {
+            let y = if last_row { rect.max.y } else { rect.min.y + sum / side };
+            let mut x = rect.min.x;
+            for (j, area) in row.iter().enumerate() {
+                let next = if j + 1 == row.len() { rect.max.x } else { x + area / (y - rect.min.y) };
+                rooms.push(BoundingRect::new(Vec2::new(x, rect.min.y), Vec2::new(next, y)));
+                x = next;
+            }
+            rect.min.y = y;
+        }
+        i = end;
+    }
+    rooms
+}
+
+/// The worst aspect ratio in a row of areas laid along a side of the given length
+fn worst(row: &[f32], side: f32) -> f32 {
+    let sum: f32 = row.iter().sum();
+    let max = row.iter().cloned().fold(f32::MIN, f32::max);
+    let min = row.iter().cloned().fold(f32::MAX, f32::min);
+    let side = side * side;
+    (side * max / (sum * sum)).max(sum * sum / (side * min))
+}
+
+#[test]
+fn squarify_test() {
+    // The example from Bruls, Huizing and van Wijk's paper
+    let bounds = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(6., 4.));
+    let rooms = squarify(&[6., 6., 4., 3., 2., 2., 1.], bounds);
+    assert_eq!(rooms.len(), 7);
+    assert_eq!(rooms[0], BoundingRect::new(Vec2::new(0., 0.), Vec2::new(3., 2.)));
+    assert_eq!(rooms[1], BoundingRect::new(Vec2::new(0., 2.), Vec2::new(3., 4.)));
+
+    let area: f32 = rooms.iter().map(|r| r.size().w * r.size().h).sum();
+    assert!((area - 24.).abs() < 1e-4);
+}
+
+#[test]
+fn treemap_limits_test() {
+    let bounds = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(32., 64.));
+    let config = ModuleCfg {
+        room_count: 12,
+        split_offset: 0.,
+        max_aspect_ratio: 1.2,
+        ..ModuleCfg::default()
+    };
+    let layout = Treemap.layout(&bounds, &config, &mut Pcg32::from_seed(73563));
+    assert!(layout.rejected > 0);
+    assert_eq!(layout.free.len(), layout.rejected);
+    for room in layout.rooms.iter() {
+        assert!(config.fits_room(room.size().w, room.size().h), "{:?} breaks the limits", room);
+    }
+}
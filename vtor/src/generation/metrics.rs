@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use super::Room;
+
+/// The measure used to colour rooms, via `Room::value`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Metric {
+    Junctions,
+    Betweenness,
+    Closeness,
+    /// Distance from the entrance room
+    Depth,
+}
+
+/// Graph measures for a single room. Distances are counted in links, not metres.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoomStats {
+    pub junctions: usize,
+    /// Share of shortest paths between other rooms that pass through this one, from 0 to 1
+    pub betweenness: f32,
+    /// Reciprocal of the mean distance to every reachable room, from 0 to 1
+    pub closeness: f32,
+    /// Distance from the entrance room, or None if it cannot be reached
+    pub depth: Option<usize>,
+    /// Only one way in or out
+    pub dead_end: bool,
+    /// Removing this room would split the module in two
+    pub articulation: bool,
+}
+
+impl RoomStats {
+    /// The given metric, unscaled
+    pub fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::Junctions => self.junctions as f32,
+            Metric::Betweenness => self.betweenness,
+            Metric::Closeness => self.closeness,
+            Metric::Depth => self.depth.map_or(0., |d| d as f32),
+        }
+    }
+}
+
+/// Every room's neighbours, sorted and without duplicates
+pub(crate) fn adjacency(rooms: &[Room]) -> Vec<Vec<usize>> {
+    rooms.iter().map(|room| {
+        let mut connected = room.connected();
+        connected.sort();
+        connected.dedup();
+        connected
+    }).collect()
+}
+
+/// Computes every measure for every room, with depth taken from `entrance`
+pub(crate) fn room_stats(adjacency: &[Vec<usize>], entrance: usize) -> Vec<RoomStats> {
+    let n = adjacency.len();
+    let betweenness = betweenness(adjacency);
+    let articulation = articulation_points(adjacency);
+    let depth = if entrance < n { distances(adjacency, entrance) } else { vec![None; n] };
+
+    (0..n).map(|i| {
+        let reached: Vec<usize> = distances(adjacency, i).into_iter().flatten().collect();
+        let total: usize = reached.iter().sum();
+        RoomStats {
+            junctions: adjacency[i].len(),
+            betweenness: betweenness[i],
+            closeness: if total > 0 { (reached.len() - 1) as f32 / total as f32 } else { 0. },
+            depth: depth[i],
+            dead_end: adjacency[i].len() == 1,
+            articulation: articulation[i],
+        }
+    }).collect()
+}
+
+/// Breadth first distances from `source`
+pub(crate) fn distances(adjacency: &[Vec<usize>], source: usize) -> Vec<Option<usize>> {
+    let mut distance = vec![None; adjacency.len()];
+    let mut queue = VecDeque::new();
+    distance[source] = Some(0);
+    queue.push_back(source);
+    while let Some(v) = queue.pop_front() {
+        let d = distance[v].unwrap();
+        for w in adjacency[v].iter() {
+            if distance[*w].is_none() {
+                distance[*w] = Some(d + 1);
+                queue.push_back(*w);
+            }
+        }
+    }
+    distance
+}
+
+/// Brandes' algorithm, normalised by the number of pairs of other rooms
+fn betweenness(adjacency: &[Vec<usize>]) -> Vec<f32> {
+    let n = adjacency.len();
+    let mut centrality = vec![0f64; n];
+
+    for s in 0..n {
+        let mut stack = Vec::new();
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths = vec![0f64; n];
+        let mut distance: Vec<Option<usize>> = vec![None; n];
+        paths[s] = 1.;
+        distance[s] = Some(0);
+
+        let mut queue = VecDeque::new();
+        queue.push_back(s);
+        while let Some(v) = queue.pop_front() {
+            stack.push(v);
+            let d = distance[v].unwrap();
+            for w in adjacency[v].iter().copied() {
+                if distance[w].is_none() {
+                    distance[w] = Some(d + 1);
+                    queue.push_back(w);
+                }
+                if distance[w] == Some(d + 1) {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        let mut dependency = vec![0f64; n];
+        while let Some(w) = stack.pop() {
+            for v in predecessors[w].iter() {
+                dependency[*v] += paths[*v] / paths[w] * (1. + dependency[w]);
+            }
+            if w != s {
+                centrality[w] += dependency[w];
+            }
+        }
+    }
+
+    // Each pair was counted from both ends
+    let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 } else { 1. };
+    centrality.iter().map(|c| (c / pairs) as f32).collect()
+}
+
+/// Tarjan's articulation points, walked with an explicit stack so large modules cannot overflow
+fn articulation_points(adjacency: &[Vec<usize>]) -> Vec<bool> {
+    let n = adjacency.len();
+    let mut order: Vec<Option<usize>> = vec![None; n];
+    let mut low = vec![0; n];
+    let mut parent: Vec<Option<usize>> = vec![None; n];
+    let mut children = vec![0; n];
+    let mut articulation = vec![false; n];
+    let mut counter = 0;
+
+    for root in 0..n {
+        if order[root].is_some() {
+            continue;
+        }
+        order[root] = Some(counter);
+        low[root] = counter;
+        counter += 1;
+
+        // Each entry is a room and the index of the next neighbour to visit
+        let mut stack = vec![(root, 0)];
+        while let Some((v, next)) = stack.pop() {
+            if next < adjacency[v].len() {
+                stack.push((v, next + 1));
+                let w = adjacency[v][next];
+                match order[w] {
+                    None => {
+                        order[w] = Some(counter);
+                        low[w] = counter;
+                        counter += 1;
+                        parent[w] = Some(v);
+                        children[v] += 1;
+                        stack.push((w, 0));
+                    }
+                    Some(o) if parent[v] != Some(w) => low[v] = low[v].min(o),
+                    _ => {}
+                }
+            } else if let Some(p) = parent[v] {
+                // Finished with v, so pass its low point up to its parent
+                low[p] = low[p].min(low[v]);
+                if parent[p].is_some() && low[v] >= order[p].unwrap() {
+                    articulation[p] = true;
+                }
+            }
+        }
+        articulation[root] = children[root] > 1;
+    }
+    articulation
+}
+
+#[test]
+fn metrics_test() {
+    // Two triangles joined through room 2, with a dead end hanging off room 4
+    //   0 - 1       3
+    //    \ /       / \
+    //     2 ----- 4 - 5 - 6
+    let edges = [(0, 1), (0, 2), (1, 2), (2, 4), (3, 4), (3, 5), (4, 5), (5, 6)];
+    let mut adjacency = vec![Vec::new(); 7];
+    for (a, b) in edges.iter() {
+        adjacency[*a].push(*b);
+        adjacency[*b].push(*a);
+    }
+
+    let stats = room_stats(&adjacency, 0);
+    let articulation: Vec<usize> = (0..7).filter(|i| stats[*i].articulation).collect();
+    assert_eq!(articulation, vec![2, 4, 5]);
+    let dead_ends: Vec<usize> = (0..7).filter(|i| stats[*i].dead_end).collect();
+    assert_eq!(dead_ends, vec![6]);
+    assert_eq!(stats[6].depth, Some(4));
+    assert_eq!(stats[2].junctions, 3);
+
+    // The bridge between the triangles carries the most traffic
+    let busiest = (0..7).max_by(|a, b| stats[*a].betweenness.partial_cmp(&stats[*b].betweenness).unwrap()).unwrap();
+    assert!(busiest == 2 || busiest == 4);
+    assert_eq!(stats[0].betweenness, 0.);
+    assert!(stats[4].closeness > stats[6].closeness);
+}
@@ -0,0 +1,133 @@
+use crate::config::Mirror;
+use super::layout::Layout;
+use super::trace::{Axis, Step};
+use prima::geom::BoundingRect;
+use vek::Vec2;
+
+/// How close two coordinates must be to count as the same after reflection
+const TOLERANCE: f32 = 1e-3;
+
+/// The centre lines reflected across, in the order they are applied
+pub(crate) fn axes(mode: Mirror) -> &'static [Axis] {
+    match mode {
+        Mirror::None => &[],
+        Mirror::Lateral => &[Axis::X],
+        Mirror::Length => &[Axis::Y],
+        Mirror::Both => &[Axis::X, Axis::Y],
+    }
+}
+
+/// The half or quarter of the bounds a mirrored layout is generated in, before being reflected into the rest
+pub(crate) fn part(mode: Mirror, bounds: &BoundingRect<f32>) -> BoundingRect<f32> {
+    let centre = bounds.center();
+    let mut part = *bounds;
+    for axis in axes(mode) {
+        match axis {
+            Axis::X => part.max.x = centre.x,
+            Axis::Y => part.max.y = centre.y,
+        }
+    }
+    part
+}
+
+/// Reflects a layout generated in `part` into the rest of the bounds
+pub(crate) fn apply(layout: &mut Layout, mode: Mirror, bounds: &BoundingRect<f32>) {
+    let centre = bounds.center();
+    for axis in axes(mode) {
+        let at = match axis {
+            Axis::X => centre.x,
+            Axis::Y => centre.y,
+        };
+        layout.rooms = reflect(&layout.rooms, *axis, at);
+        layout.corridors = reflect(&layout.corridors, *axis, at);
+        layout.free = reflect(&layout.free, *axis, at);
+        layout.steps.push(Step::Mirror { axis: *axis, at });
+    }
+}
+
+/// Adds the reflection of every rect across a line. A rect meeting its own reflection at the line is merged with it
+/// and keeps its place, and the reflections of the rest follow on in the same order.
+pub(crate) fn reflect(rects: &[BoundingRect<f32>], axis: Axis, at: f32) -> Vec<BoundingRect<f32>> {
+    let flip = Flip::across(axis, at);
+    let mut kept = Vec::new();
+    let mut images = Vec::new();
+    for rect in rects {
+        let image = flip.rect(rect);
+        let edge = match axis {
+            Axis::X => rect.max.x,
+            Axis::Y => rect.max.y,
+        };
+        if (edge - at).abs() < TOLERANCE {
+            kept.push(BoundingRect::new(Vec2::partial_min(rect.min, image.min), Vec2::partial_max(rect.max, image.max)));
+        } else {
+            kept.push(*rect);
+            images.push(image);
+        }
+    }
+    kept.extend(images);
+    kept
+}
+
+/// A reflection across one or both centre lines of a module
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Flip {
+    x: Option<f32>,
+    y: Option<f32>,
+}
+
+impl Flip {
+    fn across(axis: Axis, at: f32) -> Self {
+        match axis {
+            Axis::X => Flip { x: Some(at), y: None },
+            Axis::Y => Flip { x: None, y: Some(at) },
+        }
+    }
+
+    /// Every reflection a layout mirrored with the given mode is symmetric under
+    pub fn all(mode: Mirror, bounds: &BoundingRect<f32>) -> Vec<Self> {
+        let centre = bounds.center();
+        let (x, y) = (Flip::across(Axis::X, centre.x), Flip::across(Axis::Y, centre.y));
+        match mode {
+            Mirror::None => Vec::new(),
+            Mirror::Lateral => vec![x],
+            Mirror::Length => vec![y],
+            Mirror::Both => vec![x, y, Flip { x: x.x, y: y.y }],
+        }
+    }
+
+    pub fn point(&self, p: Vec2<f32>) -> Vec2<f32> {
+        Vec2::new(self.x.map(|at| 2. * at - p.x).unwrap_or(p.x), self.y.map(|at| 2. * at - p.y).unwrap_or(p.y))
+    }
+
+    pub fn rect(&self, rect: &BoundingRect<f32>) -> BoundingRect<f32> {
+        let (a, b) = (self.point(rect.min), self.point(rect.max));
+        BoundingRect::new(Vec2::partial_min(a, b), Vec2::partial_max(a, b))
+    }
+
+    /// True if the rects are the same once reflected
+    pub fn maps(&self, from: &BoundingRect<f32>, to: &BoundingRect<f32>) -> bool {
+        let image = self.rect(from);
+        image.min.distance(to.min) < TOLERANCE && image.max.distance(to.max) < TOLERANCE
+    }
+}
+
+#[test]
+fn reflect_test() {
+    let rects = [
+        BoundingRect::new(Vec2::new(0., 0.), Vec2::new(4., 2.)),
+        BoundingRect::new(Vec2::new(4., 0.), Vec2::new(10., 2.)),
+    ];
+
+    // The room touching the seam at x = 10 is merged with its reflection, and the other gains a twin
+    let mirrored = reflect(&rects, Axis::X, 10.);
+    assert_eq!(mirrored.len(), 3);
+    assert_eq!(mirrored[0], rects[0]);
+    assert_eq!(mirrored[1], BoundingRect::new(Vec2::new(4., 0.), Vec2::new(16., 2.)));
+    assert_eq!(mirrored[2], BoundingRect::new(Vec2::new(16., 0.), Vec2::new(20., 2.)));
+
+    let bounds = BoundingRect::new(Vec2::zero(), Vec2::new(20., 8.));
+    assert_eq!(part(Mirror::Both, &bounds), BoundingRect::new(Vec2::zero(), Vec2::new(10., 4.)));
+    let flips = Flip::all(Mirror::Both, &bounds);
+    assert_eq!(flips[2].point(Vec2::new(1., 1.)), Vec2::new(19., 7.));
+    assert!(flips[0].maps(&mirrored[0], &mirrored[2]));
+}
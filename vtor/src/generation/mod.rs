@@ -2,11 +2,31 @@ mod module_factory;
 mod room;
 mod link;
 mod portal;
+mod corridor;
+mod islands;
+mod metrics;
+pub mod layout;
+mod boundary;
+mod void;
+mod volume;
+mod circulation;
+mod trace;
+mod mirror;
+pub(crate) mod path;
+mod navmesh;
 pub mod intersect;
 
-pub use room::Room;
+pub use room::{Room, RoomKind};
+pub use metrics::{Metric, RoomStats};
 pub use portal::Portal;
 pub use link::{Link, LinkType};
 pub use module_factory::ModuleFactory;
+pub use boundary::{ModuleAddress, BoundaryPort, Port};
+pub use void::{Void, VoidKind, Hatch, HatchTarget};
+pub use volume::{VolumeFactory, Volume, VolumeLink, Face, Opening};
+pub use circulation::{Connector, ConnectorKind};
+pub use path::Path;
+pub use navmesh::{NavMesh, NavPoly, NavLink, NavArea};
+pub use trace::{Trace, Step, Pick, Axis, UnlinkReason, Replay, Snapshot};
 
 pub const IMG_SCALE: u32 = 4;
\ No newline at end of file
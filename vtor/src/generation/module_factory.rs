@@ -1,13 +1,26 @@
-use crate::config::ModuleCfg;
+use crate::config::{ModuleCfg, HumanCfg, PathCfg};
 use super::intersect::{Intersect, IntersectRef};
-use super::{Room, LinkType, IMG_SCALE};
+use super::corridor::{self, Node, Door};
+use super::islands::{collect_islands, DisjointSet};
+use super::metrics::{self, Metric};
+use super::layout::{self, LayoutStrategy};
+use super::boundary::{BoundaryPort, Port};
+use super::void::{Void, VoidKind, Hatch, HatchTarget};
+use super::trace::{Trace, Step, UnlinkReason};
+use super::path::{self, Crossing, Path};
+use super::navmesh::NavMesh;
+use super::mirror::{self, Flip};
+use super::{Room, RoomKind, Portal, LinkType, IMG_SCALE};
 use crate::geom::Compass;
+use crate::seed::{Seed, Subsystem};
 
 use vek::{Rgb, Vec2};
 use prima::geom::{BoundingRect, Line, LineExt};
 use prima::render::{RgbImage, Draw};
 use prima::core::maths::*;
-use rand::prelude::*;
+
+/// Most rects a compound room is made of, enough for a U
+const MAX_PARTS: usize = 3;
 
 #[allow(dead_code)]
 pub struct ModuleFactory {
@@ -15,186 +28,277 @@ pub struct ModuleFactory {
     pub rooms: Vec<Room>,
     pub intersects: Vec<Intersect>,
     pub islands: Vec<Vec<usize>>,
+    pub portals: Vec<Portal>,
+    /// Intersects whose wall is too short to fit a door. The rooms either side are not linked through them.
+    pub unlinkable: Vec<usize>,
+    /// Intersects whose door was removed to keep the layout closer to a tree, see `ModuleCfg::loop_factor`
+    pub pruned: Vec<usize>,
+    /// Space inside the module bounds not taken up by any room, which corridors are routed through. Emptied into `voids` by `generate_voids`.
+    pub free: Vec<BoundingRect<f32>>,
+    /// Tunnel and bridge links that no corridor could be found for
+    pub unroutable: Vec<(usize, usize)>,
+    /// Space left over once corridors have been routed, kept for maintenance and services
+    pub voids: Vec<Void>,
+    /// Service openings into the voids. These are not part of the room graph.
+    pub hatches: Vec<Hatch>,
+    /// Doors through the ends of the module into its neighbours around the ring
+    pub ports: Vec<Port>,
+    /// Rooms the layout threw away for breaking the size limits in the config
+    pub rejected: usize,
+    /// The room depth is measured from
+    pub entrance: usize,
+    /// The corridors the layout ran along the module, see `LayoutMode::Spine`. There are two when the spine is mirrored
+    /// across the module, unless they meet at the seam. Their doors are never pruned.
+    pub spines: Vec<usize>,
+    /// The measure `calculate_statistics` copies into each room's value
+    pub colouring: Metric,
+    /// Every decision made so far, which `Trace::replay` can step back through
+    pub trace: Trace,
+    /// The reflections the layout is symmetric under, see `ModuleCfg::mirror`
+    flips: Vec<Flip>,
+    config: ModuleCfg,
     most_junctions: usize,
 }
 
 impl ModuleFactory {
     /// Generates a brand new module using the given config. Will do the absolute minimum generation and leave pathfinding execution order to the end user.
     pub fn new(config: ModuleCfg) -> Self {
+        let strategy = layout::strategy(config.layout);
+        Self::with_strategy(config, strategy.as_ref())
+    }
+
+    /// As `new`, but lays rooms out with the given strategy rather than the one named in the config.
+    pub fn with_strategy(config: ModuleCfg, strategy: &dyn LayoutStrategy) -> Self {
+        Self::laid_out(config, strategy, 0., 0.)
+    }
+
+    /// Lays out rooms between a strip of the given depth at the south end of the module and another at the north end
+    fn laid_out(config: ModuleCfg, strategy: &dyn LayoutStrategy, south: f32, north: f32) -> Self {
         let mut bounds = BoundingRect::new_empty(Vec2::zero());
         bounds.max = Vec2::new(config.extent().w, config.extent().h);
         bounds.make_valid();
 
-        let mut rooms = vec![Room::new(bounds.clone())];
-        let mut rng = StdRng::seed_from_u64(config.seed);
-
-        let mut offset = clamp01(config.split_offset);
-        let degredation = clamp01(config.split_degredation);
-
-        for _ in 0..config.divisions {
-
-            let mut index = 0;
-
-            let v: f32 = rng.gen();
+        let inner = BoundingRect::new(Vec2::new(bounds.min.x, bounds.min.y + south), Vec2::new(bounds.max.x, bounds.max.y - north));
+        let mut rng = Seed::new(config.seed).subsystem(Subsystem::Layout).rng();
+        let parts = 1 << mirror::axes(config.mirror).len();
+        let part = ModuleCfg {
+            room_count: config.room_count.div_ceil(parts),
+            divisions: config.divisions.div_ceil(parts),
+            ..config.clone()
+        };
+        let mut layout = strategy.layout(&mirror::part(config.mirror, &inner), &part, &mut rng);
+        mirror::apply(&mut layout, config.mirror, &inner);
+        let mut rooms: Vec<Room> = layout.rooms.into_iter().map(Room::new).collect();
+        let spines: Vec<usize> = (rooms.len()..rooms.len() + layout.corridors.len()).collect();
+        rooms.extend(layout.corridors.into_iter().map(Room::corridor));
+        let free = layout.free;
+
+        let mut module = Self {
+            bounds,
+            intersects: Vec::new(),
+            rooms,
+            islands: Vec::new(),
+            portals: Vec::new(),
+            unlinkable: Vec::new(),
+            pruned: Vec::new(),
+            free,
+            unroutable: Vec::new(),
+            voids: Vec::new(),
+            hatches: Vec::new(),
+            ports: Vec::new(),
+            rejected: layout.rejected,
+            entrance: spines.first().copied().unwrap_or(0),
+            spines,
+            colouring: Metric::Betweenness,
+            trace: Trace { steps: layout.steps },
+            flips: Flip::all(config.mirror, &inner),
+            config,
+            most_junctions: 0,
+        };
+        module.merge_rooms();
+        module
+    }
 
-            if v > config.divide_area_chance {
-                let mut largest_area = 0.;
-                for (i, room) in rooms.iter().enumerate() {
-                    let area = room.size().w * room.size().h;
-                    if area > largest_area {
-                        largest_area = area;
-                        index = i;
-                    }
+    /// Merges some rooms into a neighbour to form L, T and U shaped rooms, see `ModuleCfg::merge_rate`.
+    /// Mirrored rooms are merged along with their images, so the layout stays symmetric.
+    fn merge_rooms(&mut self) {
+        if self.config.merge_rate <= 0. {
+            return;
+        }
+        let mut rng = Seed::new(self.config.seed).subsystem(Subsystem::Layout).child("merge").rng();
+        // Wide enough to walk through, as the wall between the two is knocked out
+        let min_wall = self.config.min_room_width.min(self.config.min_room_depth);
+
+        let mut i = 0;
+        while i < self.rooms.len() {
+            if !self.can_merge(i) || !rng.gen_bool(self.config.merge_rate as f64) {
+                i += 1;
+                continue;
+            }
+            let room = &self.rooms[i];
+            let neighbours: Vec<usize> = (0..self.rooms.len())
+                .filter(|j| *j != i && self.can_merge(*j))
+                .filter(|j| {
+                    let other = &self.rooms[*j];
+                    let wall = shared_wall(room, other).map(|(line, _)| line.start.distance(line.end)).unwrap_or(0.);
+                    // Rooms sharing a whole wall would only make a bigger rectangle
+                    let whole: BoundingRect<f32> = BoundingRect::new(Vec2::partial_min(room.rect.min, other.rect.min), Vec2::partial_max(room.rect.max, other.rect.max));
+                    let rectangular = whole.size().w * whole.size().h <= room.area() + other.area() + 1e-3;
+                    let stepped = room.parts().iter().any(|p| other.parts().iter().any(|q| !flush(p, q)));
+                    wall >= min_wall && !rectangular && !stepped && room.parts().len() + other.parts().len() <= MAX_PARTS
+                })
+                .collect();
+            if neighbours.is_empty() {
+                i += 1;
+                continue;
+            }
+            // Rooms are found by a part of their floor, as earlier merges move them about the list
+            let (part, other) = (room.parts()[0], self.rooms[neighbours[rng.gen_range(0, neighbours.len())]].parts()[0]);
+
+            // Removing a room at or before `i` shifts the rest down, so the next room to try moves with them
+            let mut next = i + 1;
+            let mut pairs = vec![(part, other)];
+            for flip in self.flips.iter() {
+                let pair = (flip.rect(&part), flip.rect(&other));
+                if !pairs.iter().any(|p| same_pair(p, &pair)) {
+                    pairs.push(pair);
                 }
-            } else if v > config.divide_disparity_chance {
-                let mut largest_disparity = 0.;
-                for (i, room) in rooms.iter().enumerate() {
-                    let disp = (room.size().w - room.size().h).abs();
-                    if disp > largest_disparity {
-                        largest_disparity = disp;
-                        index = i;
+            }
+            for (a, b) in pairs {
+                let find = |part: &BoundingRect<f32>| self.rooms.iter().position(|r| {
+                    r.kind == RoomKind::Room && r.parts().iter().any(|p| p.min.distance(part.min) < 1e-4 && p.max.distance(part.max) < 1e-4)
+                });
+                if let (Some(a), Some(b)) = (find(&a), find(&b)) {
+                    if a != b && self.rooms[a].parts().len() + self.rooms[b].parts().len() <= MAX_PARTS {
+                        let absorbed = self.rooms[b].clone();
+                        self.rooms[a].absorb(&absorbed);
+                        self.rooms.remove(b);
+                        self.trace.push(Step::Merge { a, b });
+                        if b < next {
+                            next -= 1;
+                        }
                     }
                 }
-            } else {
-                index = rng.gen_range::<usize, usize, usize>(0, rooms.len());
-            }
-            
-            let random_room = rooms[index].clone();
-            let extent = random_room.size();
-            let r: f32 = rng.gen_range::<f32, f32, f32>(offset, 1. - offset);
-            // Degrade offset so it becomes less centered
-            offset *= degredation;
-
-            rooms.remove(index);
-            if extent.w > extent.h {
-                let split = random_room.rect.split_at_x(random_room.rect.min.x + r * extent.w);
-                let room_a = Room::new(split[0].clone().made_valid());
-                let room_b = Room::new(split[1].clone().made_valid());
-                rooms.push(room_a);
-                rooms.push(room_b);
-            } else {
-                let split = random_room.rect.split_at_y(random_room.rect.min.y + r * extent.h);
-                let room_a = Room::new(split[0].clone().made_valid());
-                let room_b = Room::new(split[1].clone().made_valid());
-                rooms.push(room_b);
-                rooms.push(room_a);
             }
+            i = next;
         }
 
-        // Remove some random rooms
-        for _ in 0.. config.divisions - config.room_count {
-            let index = rng.gen_range(0, rooms.len());
-            rooms.remove(index);
-        }
-
-        println!("Room count: {}", rooms.len());
-
-        Self {
-            bounds,
-            intersects: Vec::new(),
-            rooms,
-            islands: Vec::new(),
-            most_junctions: 0,
+        if !self.spines.is_empty() {
+            self.spines = (0..self.rooms.len()).filter(|i| self.rooms[*i].kind == RoomKind::Corridor).collect();
+            self.entrance = self.spines[0];
         }
     }
 
     /// Generates a module with the given config and executes default pathfinding workflow.
-    pub fn default(config: ModuleCfg) -> Self {
-        let mut module = Self::new(config);
+    pub fn default(config: ModuleCfg, humans: &HumanCfg) -> Self {
+        Self::with_ports(config, humans, &[])
+    }
+
+    /// As `default`, but runs a corridor across each end of the module that has a boundary port, with the port opening off it.
+    /// The corridors are linked into the room graph like any other room, so every port can reach every other.
+    pub fn with_ports(config: ModuleCfg, humans: &HumanCfg, ports: &[BoundaryPort]) -> Self {
+        let width = config.door_clearance(humans);
+        let depth = |edge: Compass| if ports.iter().any(|p| p.edge == edge) { width } else { 0. };
+        let strategy = layout::strategy(config.layout);
+        let mut module = Self::laid_out(config, strategy.as_ref(), depth(Compass::South), depth(Compass::North));
+        module.add_ports(ports, width);
+
         module.link_rooms(true);
+        module.generate_portals(humans);
+        module.link_spanning_tree();
         module.generate_islands();
         module.link_islands();
+        debug_assert!(module.is_connected());
+        module.generate_corridors(humans);
+        module.generate_voids(humans);
         module.calculate_statistics();
-        return module
+        module
     }
 
-    /// As far as possible, connects all rooms to their neighbors. 
+    /// As far as possible, connects all rooms to their neighbors. Each adjacent pair shares a single intersect, holding the wall between them.
     /// If allow_nearest, isolated rooms are allowed to link to their nearest neighbor. 
     pub fn link_rooms(&mut self, allow_nearest: bool) {
         for i in 0..self.rooms.len() {
             for j in find_adjacant_rooms(&self.rooms, i) {
-                if i == j {
+                if j <= i {
+                    // Pairs are only visited once, from the lower index
                     continue;
                 }
 
-                if j < i {
-                    // intersect has probably allready been found- grab it off the stack
-                    
-                    
-                } else {
-                    // Find the intersection line
-                    let room = self.rooms[i];
-                    let other = self.rooms[j];
-                    let west = room.rect.min.x == other.rect.max.x;
-                    let east = room.rect.max.x == other.rect.min.x;
-                    let north = room.rect.max.y == other.rect.min.y;
-                    let south = room.rect.min.y == other.rect.max.y;
-
-                    let line = if north || south {
-                        let x_max = room.rect.min.x.max(other.rect.min.x);
-                        let x_min = room.rect.max.x.min(other.rect.max.x);
-                        let y = if north {
-                            room.rect.max.y
-                        } else {
-                            room.rect.min.y
-                        };
-
-                        Line {
-                            start: Vec2::new(x_min, y),
-                            end: Vec2::new(x_max, y),
-                        }
-                    } else {
-                        let y_max = room.rect.min.y.max(other.rect.min.y);
-                        let y_min = room.rect.max.y.min(other.rect.max.y);
-                        let x = if east {
-                            room.rect.max.x
-                        } else if west {
-                            room.rect.min.x
-                        } else {
-                            panic!("cannot generate portal: unable to identify x");
-                        };
-
-                        Line {
-                            start: Vec2::new(x, y_min),
-                            end: Vec2::new(x, y_max),
-                        }
-                    };
-
-                    let edge = if west {
-                        Compass::West
-                    } else if east {
-                        Compass::East
-                    } else if north {
-                        Compass::North
-                    } else {
-                        Compass::South
-                    };
-                }
-                //connect_rooms(&mut self.rooms, i, j, LinkType::Direct);
+                // Rooms that only meet at a corner have no wall to share
+                let (line, edge) = match shared_wall(&self.rooms[i], &self.rooms[j]) {
+                    Some(shared) => shared,
+                    None => continue,
+                };
+
+                let index = self.intersects.len();
+                self.intersects.push(Intersect {
+                    line,
+                    rooms: (i, j),
+                    edge,
+                    portal: None,
+                });
+                self.rooms[i].link(j, LinkType::Direct(IntersectRef { index, edge }));
+                self.rooms[j].link(i, LinkType::Direct(IntersectRef { index, edge: edge.opposite() }));
+                self.trace.push(Step::Link { a: i, b: j, link_type: LinkType::Direct(IntersectRef { index, edge }) });
             }
-            if self.rooms[i].connected().len() == 0 && allow_nearest {
+            if self.rooms[i].connected().is_empty() && allow_nearest {
                 //Nearest room instead
                 let j = find_nearest_room(&self.rooms, i);
-                connect_rooms(&mut self.rooms, i, j, LinkType::Tunnel);
+                self.connect(i, j, LinkType::Tunnel);
             }
         }
     }
 
+    /// Adds a corridor of the given depth across each end of the module holding a port, and opens the ports off them.
+    /// The first port's corridor becomes the entrance.
+    fn add_ports(&mut self, ports: &[BoundaryPort], depth: f32) {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        for edge in [Compass::South, Compass::North].iter() {
+            if !ports.iter().any(|p| p.edge == *edge) {
+                continue;
+            }
+            let rect = if *edge == Compass::South {
+                BoundingRect::new(min, Vec2::new(max.x, min.y + depth))
+            } else {
+                BoundingRect::new(Vec2::new(min.x, max.y - depth), max)
+            };
+            let room = self.rooms.len();
+            self.rooms.push(Room::corridor(rect));
+            self.trace.push(Step::Place { rect, kind: RoomKind::Corridor });
+
+            let y = if *edge == Compass::South { min.y } else { max.y };
+            for boundary in ports.iter().filter(|p| p.edge == *edge) {
+                let position = Vec2::new((min.x + max.x) / 2. + boundary.offset, y);
+                self.ports.push(Port { boundary: *boundary, position, room });
+            }
+        }
+        if let Some(port) = self.ports.first() {
+            self.entrance = port.room;
+        }
+    }
+
+    /// Groups rooms into islands of linked rooms, ordered by their lowest room index.
     pub fn generate_islands(&mut self) {
-        let mut islands = Vec::new();
-        recursively_collect_islands(&self.rooms, &mut islands, 0, 0);
-        self.islands = islands;
+        self.islands = collect_islands(&self.rooms);
     }
 
+    /// Bridges islands together until every room can reach every other. The island holding room 0 is repeatedly joined to its nearest outside room.
     pub fn link_islands(&mut self) {
-        for island in self.islands.iter() {
+        self.generate_islands();
+        while self.islands.len() > 1 {
+            let island = &self.islands[0];
+            let mut inside = vec![false; self.rooms.len()];
+            for i in island.iter() {
+                inside[*i] = true;
+            }
+
             let mut nearest = f32::MAX;
             let mut nearest_index = (0, 0);
-
             for island_room_index in island.iter() {
                 for (i, room) in self.rooms.iter().enumerate() {
-                    if !island.contains(&i) {
-                        let dist = self.rooms[*island_room_index].rect.center().distance(room.rect.center());
+                    if !inside[i] {
+                        let dist = self.rooms[*island_room_index].centre().distance(room.centre());
                         if dist < nearest {
                             nearest = dist;
                             nearest_index = (*island_room_index, i);
@@ -202,19 +306,37 @@ impl ModuleFactory {
                     }
                 }
             }
-            if nearest != f32::MAX {
-                connect_rooms(&mut self.rooms, nearest_index.0, nearest_index.1, LinkType::Bridge);
+            self.connect(nearest_index.0, nearest_index.1, LinkType::Bridge);
+            // Mirrored layouts get the same bridge in every mirrored position
+            for flip in self.flips.clone().iter() {
+                if let (Some(a), Some(b)) = (self.image(nearest_index.0, flip), self.image(nearest_index.1, flip)) {
+                    if a != b && !self.rooms[a].is_linked(b) {
+                        self.connect(a, b, LinkType::Bridge);
+                    }
+                }
             }
+            self.generate_islands();
         }
     }
 
+    /// True if every room can be reached from every other
+    pub fn is_connected(&self) -> bool {
+        collect_islands(&self.rooms).len() <= 1
+    }
+
+    /// Panics, listing the islands found, unless every room can be reached from every other
+    pub fn assert_connected(&self) {
+        let islands = collect_islands(&self.rooms);
+        assert!(islands.len() <= 1, "module has {} islands: {:?}", islands.len(), islands);
+    }
+
     pub fn link_raycasting(&mut self) {
         // Find main room
         let mut largest_area = 0.;
         let mut main_room = 0;
 
         for (i, room) in self.rooms.iter().enumerate() {
-            let area = room.size().w * room.size().h;
+            let area = room.area();
             if area > largest_area {
                 largest_area = area;
                 main_room = i;
@@ -222,14 +344,14 @@ impl ModuleFactory {
         }
 
         // Lets try and link every room to the main room directly, using ray casting
-        let main_room_pos = self.rooms[main_room].rect.center();
+        let main_room_pos = self.rooms[main_room].centre();
         for i in 0..self.rooms.len() {
             if i == main_room {
                 continue;
             }
 
             let ray = Line {
-                start: self.rooms[i].rect.center(),
+                start: self.rooms[i].centre(),
                 end: main_room_pos,
             };
 
@@ -240,108 +362,553 @@ impl ModuleFactory {
                     continue;
                 }
 
-                if ray.intersects_rect(&r2.rect.into_rect()) {
+                if r2.parts().iter().any(|part| ray.intersects_rect(&part.into_rect())) {
                     intersect = true;
                     break;
                 }
             }
 
             if !intersect && !self.rooms[i].is_linked(main_room) {
-                connect_rooms(&mut self.rooms, i, main_room, LinkType::Main);
+                self.connect(i, main_room, LinkType::Main);
             }
         }
     }
 
+    /// Computes graph measures for every room, then sets each room's value to the `colouring` measure, scaled to [0, 1] across the module.
     pub fn calculate_statistics(&mut self) {
-        // Statistical analysis
-        let mut most_junctions = 1;
+        let stats = metrics::room_stats(&metrics::adjacency(&self.rooms), self.entrance);
+        self.most_junctions = stats.iter().map(|s| s.junctions).max().unwrap_or(0);
+
+        let values: Vec<f32> = stats.iter().map(|s| s.get(self.colouring)).collect();
+        let min = values.iter().cloned().fold(f32::MAX, f32::min);
+        let max = values.iter().cloned().fold(f32::MIN, f32::max);
+
+        for (room, (stats, v)) in self.rooms.iter_mut().zip(stats.into_iter().zip(values)) {
+            room.stats = stats;
+            room.value = if max > min { lerpc(0., 1., inverse_lerp(min, max, v)) } else { 0. };
+        }
+    }
+
+    /// Rooms whose loss would split the module in two
+    pub fn articulation_points(&self) -> Vec<usize> {
+        (0..self.rooms.len()).filter(|i| self.rooms[*i].stats.articulation).collect()
+    }
+
+    /// Rooms with only one way in or out
+    pub fn dead_ends(&self) -> Vec<usize> {
+        (0..self.rooms.len()).filter(|i| self.rooms[*i].stats.dead_end).collect()
+    }
 
-        for r in self.rooms.iter() {
-            let len = r.connected().len();
-            if  len > most_junctions {
-                most_junctions = len;
+    /// Places a door somewhere along every shared wall. Doors are wide enough for a human, plus a frame either side.
+    /// Walls too short to fit one are recorded in `unlinkable`, and the rooms either side lose their direct link.
+    /// In mirrored layouts, each door is reflected onto the mirrored walls, and walls crossing a seam get their door on it.
+    pub fn generate_portals(&mut self, humans: &HumanCfg) {
+        let mut rng = Seed::new(self.config.seed).subsystem(Subsystem::Links).child("portals").rng();
+        let door_width = humans.width();
+        let clearance = self.config.door_clearance(humans);
+        let mut mirrored: Vec<Option<Vec2<f32>>> = vec![None; self.intersects.len()];
+
+        for i in 0..self.intersects.len() {
+            let intersect = self.intersects[i];
+            let length = intersect.length();
+            if length < clearance {
+                let (a, b) = intersect.rooms;
+                self.disconnect(a, b, UnlinkReason::Narrow);
+                self.unlinkable.push(i);
+                continue;
             }
+
+            let position = match mirrored[i] {
+                Some(position) => position,
+                None => {
+                    // Keep the door and its frame clear of either end of the wall
+                    let margin = (clearance / 2.) / length;
+                    let t = if margin < 0.5 { rng.gen_range(margin, 1. - margin) } else { 0.5 };
+                    let mut position = intersect.line.start + (intersect.line.end - intersect.line.start) * t;
+                    for flip in self.flips.iter() {
+                        match self.intersect_image(i, flip) {
+                            Some(j) if j == i => position = (position + flip.point(position)) / 2.,
+                            _ => {}
+                        }
+                    }
+                    for flip in self.flips.iter() {
+                        if let Some(j) = self.intersect_image(i, flip) {
+                            mirrored[j] = Some(flip.point(position));
+                        }
+                    }
+                    position
+                }
+            };
+
+            self.intersects[i].portal = Some(self.portals.len());
+            self.portals.push(Portal::new(position, intersect.edge, door_width, intersect.rooms));
         }
+    }
 
-        for i in 0..self.rooms.len() {
-            let junctions = self.rooms[i].connected().len() as f32;
-            let focus = inverse_lerp(1., most_junctions as f32, junctions);
-            self.rooms[i].value = lerpc(0.,1., focus);
+    /// Thins direct links down to a spanning tree over the doors, then adds back `loop_factor` of the remaining doors at random.
+    /// Doors are weighted by the walk from one room's centre, through the door, to the other's. Doors onto the spine are
+    /// taken first, so every room keeps its door onto it. Returns the cyclomatic number achieved.
+    pub fn link_spanning_tree(&mut self) -> usize {
+        let mut doors: Vec<(bool, f32, usize)> = Vec::new();
+        for (i, intersect) in self.intersects.iter().enumerate() {
+            if let Some(p) = intersect.portal {
+                let (a, b) = intersect.rooms;
+                let position = self.portals[p].position;
+                let cost = self.rooms[a].centre().distance(position) + position.distance(self.rooms[b].centre());
+                let off_spine = !self.spines.contains(&a) && !self.spines.contains(&b);
+                doors.push((off_spine, cost, i));
+            }
         }
+        doors.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.partial_cmp(&b.1).unwrap()).then(a.2.cmp(&b.2)));
+
+        // Kruskal's algorithm. Any door joining two rooms that are already connected would close a loop.
+        // Mirrored doors are kept or dropped together, so a mirrored layout may keep a few loops even at a loop factor of 0.
+        let mut set = DisjointSet::new(self.rooms.len());
+        let mut loops = Vec::new();
+        let mut seen = vec![false; self.intersects.len()];
+        for (_, _, i) in doors {
+            if seen[i] {
+                continue;
+            }
+            let orbit = self.orbit(i);
+            let mut joined = false;
+            for j in orbit.iter() {
+                seen[*j] = true;
+                let (a, b) = self.intersects[*j].rooms;
+                joined |= set.union(a, b);
+            }
+            if !joined {
+                loops.push(orbit);
+            }
+        }
+
+        let mut rng = Seed::new(self.config.seed).subsystem(Subsystem::Links).child("loops").rng();
+        rng.shuffle(&mut loops);
+        let keep = (clamp01(self.config.loop_factor) * loops.len() as f32).round() as usize;
+
+        let mut removed = vec![false; self.portals.len()];
+        for i in loops.drain(keep..).flatten() {
+            let (a, b) = self.intersects[i].rooms;
+            self.disconnect(a, b, UnlinkReason::Pruned);
+            removed[self.intersects[i].portal.take().unwrap()] = true;
+            self.pruned.push(i);
+        }
+        self.pruned.sort();
+
+        // Drop the removed doors, and point the remaining intersects at their door's new index
+        let mut remap = vec![None; self.portals.len()];
+        let mut portals = Vec::new();
+        for (i, portal) in self.portals.iter().enumerate() {
+            if !removed[i] {
+                remap[i] = Some(portals.len());
+                portals.push(*portal);
+            }
+        }
+        self.portals = portals;
+        for intersect in self.intersects.iter_mut() {
+            intersect.portal = intersect.portal.and_then(|p| remap[p]);
+        }
+
+        self.cyclomatic_number()
     }
 
-    pub fn generate_portals(&mut self) {
-        for i in 0..self.rooms.len() {
-            let room = &self.rooms[i];
-            let mut intersects = Vec::new();
+    /// The number of independent loops in the room graph: links - rooms + islands. Zero for a tree.
+    pub fn cyclomatic_number(&self) -> usize {
+        let links: usize = self.rooms.iter().enumerate()
+            .map(|(i, room)| room.connected().iter().filter(|j| **j > i).count())
+            .sum();
+        (links + collect_islands(&self.rooms).len()).saturating_sub(self.rooms.len())
+    }
+
+    /// Replaces every tunnel and bridge link with a real corridor, wide enough for a human plus a door frame either side.
+    /// Corridors are routed through free space where possible, and only pass through other rooms when there is no other way.
+    /// Each corridor is made up of one or more corridor rooms, joined to each other and to the rooms at either end by portals.
+    pub fn generate_corridors(&mut self, humans: &HumanCfg) {
+        let width = self.config.door_clearance(humans);
 
+        let mut pending = Vec::new();
+        for (i, room) in self.rooms.iter().enumerate() {
             for link in room.links() {
-                let other = &self.rooms[link.target];
-                if link.link_type == LinkType::Direct {
-                    // This link requires only a single portal- find the cross-over
-                    let west = room.rect.min.x == other.rect.max.x;
-                    let east = room.rect.max.x == other.rect.min.x;
-                    let north = room.rect.max.y == other.rect.min.y;
-                    let south = room.rect.min.y == other.rect.max.y;
-
-                    let line = if north || south {
-                        let x_max = room.rect.min.x.max(other.rect.min.x);
-                        let x_min = room.rect.max.x.min(other.rect.max.x);
-                        let y = if north {
-                            room.rect.max.y
-                        } else {
-                            room.rect.min.y
-                        };
-
-                        Line {
-                            start: Vec2::new(x_min, y),
-                            end: Vec2::new(x_max, y),
+                match link.link_type {
+                    LinkType::Tunnel | LinkType::Bridge if link.target > i => pending.push((i, link.target, link.link_type)),
+                    _ => {}
+                }
+            }
+        }
+
+        for (a, b, link_type) in pending {
+            // Corridors are only cut through plain rectangles, so compound rooms are left whole
+            let node = |i: usize, room: &Room| match room.kind {
+                RoomKind::Corridor => Node::Room(i, true),
+                RoomKind::Room if room.is_compound() && i != a && i != b => Node::Solid(i),
+                RoomKind::Room => Node::Room(i, false),
+            };
+            // The first part of each room comes first, so rooms and their nodes share an index
+            let mut nodes: Vec<(Node, BoundingRect<f32>)> = self.rooms.iter().enumerate()
+                .map(|(i, room)| (node(i, room), room.parts()[0]))
+                .collect();
+            for (i, room) in self.rooms.iter().enumerate() {
+                nodes.extend(room.parts()[1..].iter().map(|part| (node(i, room), *part)));
+            }
+            nodes.extend(self.free.iter().enumerate().map(|(i, rect)| (Node::Free(i), *rect)));
+
+            let (route, doors) = match corridor::route(&nodes, a, b, width) {
+                Some(route) => route,
+                None => {
+                    self.unroutable.push((a, b));
+                    continue;
+                }
+            };
+
+            self.disconnect(a, b, UnlinkReason::Routed);
+
+            // Lay down corridor rooms across each free rect, remembering the first and last of each so they can be joined up
+            let mut ends: Vec<(usize, usize)> = Vec::new();
+            let mut carved: Vec<(usize, BoundingRect<f32>)> = Vec::new();
+            for (k, node) in route.iter().enumerate() {
+                match *node {
+                    Node::Room(i, false) if i != a && i != b => {
+                        let entry = Door { position: doors[k - 1].position, edge: doors[k - 1].edge.opposite() };
+                        ends.push(self.cut_through(i, entry, doors[k], humans, link_type));
+                    }
+                    Node::Room(i, _) | Node::Solid(i) => ends.push((i, i)),
+                    Node::Free(f) => {
+                        let entry = Door { position: doors[k - 1].position, edge: doors[k - 1].edge.opposite() };
+                        let (pieces, joins) = corridor::carve(&self.free[f], entry, doors[k], width);
+                        let first = self.rooms.len();
+                        for piece in pieces.iter() {
+                            self.rooms.push(Room::corridor(*piece));
+                            self.trace.push(Step::Place { rect: *piece, kind: RoomKind::Corridor });
+                            carved.push((f, *piece));
                         }
-                    } else {
-                        let y_max = room.rect.min.y.max(other.rect.min.y);
-                        let y_min = room.rect.max.y.min(other.rect.max.y);
-                        let x = if east {
-                            room.rect.max.x
-                        } else if west {
-                            room.rect.min.x
-                        } else {
-                            panic!("cannot generate portal: unable to identify x");
-                        };
-
-                        Line {
-                            start: Vec2::new(x, y_min),
-                            end: Vec2::new(x, y_max),
+                        for (j, join) in joins.iter().enumerate() {
+                            self.add_corridor_portal(join.position, join.edge, width, first + j, first + j + 1, link_type);
                         }
-                    };
+                        ends.push((first, self.rooms.len() - 1));
+                    }
+                }
+            }
 
-                    let edge = if west {
-                        Compass::West
-                    } else if east {
-                        Compass::East
-                    } else if north {
-                        Compass::North
-                    } else {
-                        Compass::South
-                    };
-                    
-                    let intersect: Intersect = Intersect {
-                        line,
-                    };
+            for (k, door) in doors.iter().enumerate() {
+                if ends[k].1 == ends[k + 1].0 {
+                    // Between two parts of the same room, where there is no wall
+                    continue;
+                }
+                self.add_corridor_portal(door.position, door.edge, humans.width(), ends[k].1, ends[k + 1].0, link_type);
+            }
 
-                    let intersect_ref = IntersectRef {
-                        intersect: 0,
-                        other: 
-                    }
+            // Whatever is left of each free rect stays available to later corridors
+            let mut free = Vec::new();
+            for (f, rect) in self.free.iter().enumerate() {
+                let mut parts = vec![*rect];
+                for (_, cut) in carved.iter().filter(|(g, _)| *g == f) {
+                    parts = parts.iter().flat_map(|part| corridor::subtract(part, cut)).collect();
+                }
+                free.extend(parts);
+            }
+            self.free = free;
+        }
+    }
+
+    /// True if the room could take in another part, or be taken in itself
+    fn can_merge(&self, i: usize) -> bool {
+        self.rooms[i].kind == RoomKind::Room && self.rooms[i].parts().len() < MAX_PARTS
+    }
+
+    /// Cuts a corridor through a room from one door to another, returning the first and last pieces of corridor.
+    /// The room keeps the largest piece left either side, and the rest become rooms of their own if they fit the size
+    /// limits, or free space if not. Doors in the room's walls move to whichever room or corridor now holds them.
+    /// If that would strand a door or leave a room with no way out, the whole room becomes corridor instead.
+    fn cut_through(&mut self, room: usize, entry: Door, exit: Door, humans: &HumanCfg, link_type: LinkType) -> (usize, usize) {
+        let width = self.config.door_clearance(humans);
+        let rect = self.rooms[room].rect;
+        let (pieces, joins) = corridor::carve(&rect, entry, exit, width);
+        let area = |r: &BoundingRect<f32>| r.size().w * r.size().h;
+        let mut rest = vec![rect];
+        for piece in pieces.iter() {
+            rest = rest.iter().flat_map(|r| corridor::subtract(r, piece)).collect();
+        }
+        rest.retain(|r| area(r) > 1e-3);
+        rest.sort_by(|a, b| area(b).partial_cmp(&area(a)).unwrap());
+
+        let doors: Vec<usize> = (0..self.portals.len()).filter(|p| self.portals[*p].connects(room)).collect();
+        let holds = |r: &BoundingRect<f32>| doors.iter().any(|p| on_wall(r, &self.portals[*p]));
+        // The piece of corridor each leftover can open onto
+        let onto = |r: &BoundingRect<f32>| pieces.iter().enumerate().find_map(|(k, piece)| match shared_edge(r, piece) {
+            Some((line, edge)) if line.start.distance(line.end) >= width => Some((k, line, edge)),
+            _ => None,
+        });
+
+        let mut kept = Vec::new();
+        let mut free = Vec::new();
+        let mut whole = false;
+        for r in rest {
+            match onto(&r) {
+                Some(door) if holds(&r) || self.config.fits_room(r.size().w, r.size().h) => kept.push((r, door)),
+                _ if holds(&r) => whole = true,
+                _ => free.push(r),
+            }
+        }
+        whole |= kept.is_empty();
+        whole |= doors.iter().any(|p| !kept.iter().map(|(k, _)| k).chain(pieces.iter()).any(|r| on_wall(r, &self.portals[*p])));
+        if whole {
+            self.rooms[room].kind = RoomKind::Corridor;
+            self.trace.push(Step::Reshape { room, rect, kind: RoomKind::Corridor });
+            return (room, room);
+        }
+
+        let first = self.rooms.len();
+        for piece in pieces.iter() {
+            self.rooms.push(Room::corridor(*piece));
+            self.trace.push(Step::Place { rect: *piece, kind: RoomKind::Corridor });
+        }
+        for (j, join) in joins.iter().enumerate() {
+            self.add_corridor_portal(join.position, join.edge, width, first + j, first + j + 1, link_type);
+        }
+
+        let mut rooms = Vec::new();
+        for (n, (r, _)) in kept.iter().enumerate() {
+            if n == 0 {
+                self.rooms[room].rect = *r;
+                self.trace.push(Step::Reshape { room, rect: *r, kind: RoomKind::Room });
+                rooms.push(room);
+            } else {
+                rooms.push(self.rooms.len());
+                self.rooms.push(Room::new(*r));
+                self.trace.push(Step::Place { rect: *r, kind: RoomKind::Room });
+            }
+        }
+
+        let holders: Vec<(BoundingRect<f32>, usize)> = kept.iter().map(|(k, _)| *k).zip(rooms.iter().copied())
+            .chain(pieces.iter().copied().zip(first..))
+            .collect();
+        for p in doors {
+            let holder = holders.iter().find(|(r, _)| on_wall(r, &self.portals[p])).map(|(_, i)| *i).unwrap();
+            if holder != room {
+                self.move_door(p, room, holder);
+            }
+        }
+        self.settle_walls(room, &holders);
+        for ((_, (k, line, edge)), i) in kept.iter().zip(rooms) {
+            self.add_corridor_portal((line.start + line.end) / 2., *edge, humans.width(), i, first + k, link_type);
+        }
+        for r in free {
+            self.free.push(r);
+            self.trace.push(Step::Free { rect: r });
+        }
+        (first, first + pieces.len() - 1)
+    }
+
+    /// Moves a door from one side of a room's wall to another room now on that side, carrying its link and wall across
+    fn move_door(&mut self, portal: usize, from: usize, to: usize) {
+        let (a, b) = self.portals[portal].rooms;
+        let other = if a == from { b } else { a };
+        let (there, back) = (self.rooms[from].link_type(other), self.rooms[other].link_type(from));
+        self.disconnect(from, other, UnlinkReason::Routed);
+        if let (Some(there), Some(back)) = (there, back) {
+            self.rooms[to].link(other, there);
+            self.rooms[other].link(to, back);
+            self.trace.push(Step::Link { a: to, b: other, link_type: there });
+        }
+        if let Some(LinkType::Direct(r)) = there {
+            self.move_wall(r.index, from, to);
+        }
+        self.portals[portal].rooms = (to.min(other), to.max(other));
+    }
+
+    /// Hands a wall from one room to another on the same side of it, keeping only the stretch the new room covers.
+    /// The edge it lies on is the same for both, so links through it need not change.
+    fn move_wall(&mut self, i: usize, from: usize, to: usize) {
+        let intersect = &mut self.intersects[i];
+        let other = intersect.other(from);
+        let edge = if intersect.rooms.0 == from { intersect.edge } else { intersect.edge.opposite() };
+        intersect.line = along(&intersect.line, &self.rooms[to].rect).unwrap_or(intersect.line);
+        intersect.rooms = (to.min(other), to.max(other));
+        intersect.edge = if to < other { edge } else { edge.opposite() };
+    }
+
+    /// After a room is cut up, gives each of its remaining walls to whichever of the pieces now lies along most of it,
+    /// doors staying with the piece that holds them. Walls left facing only free space are dropped.
+    fn settle_walls(&mut self, room: usize, holders: &[(BoundingRect<f32>, usize)]) {
+        let mut dropped = vec![false; self.intersects.len()];
+        for (i, drop) in dropped.iter_mut().enumerate() {
+            let intersect = self.intersects[i];
+            if intersect.rooms.0 != room && intersect.rooms.1 != room {
+                continue;
+            }
+            // A door's wall has already moved with it if it had to
+            let holder = match intersect.portal {
+                Some(_) => Some(room),
+                None => holders.iter()
+                    .filter_map(|(r, h)| along(&intersect.line, r).map(|line| (line.start.distance(line.end), *h)))
+                    .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .map(|(_, h)| h),
+            };
+            match holder {
+                Some(holder) => self.move_wall(i, room, holder),
+                None => *drop = true,
+            }
+        }
+        if !dropped.contains(&true) {
+            return;
+        }
+
+        // Drop the walls, and point everything that refers to a remaining wall at its new index
+        let mut remap = vec![None; self.intersects.len()];
+        let mut intersects = Vec::new();
+        for (i, intersect) in self.intersects.iter().enumerate() {
+            if !dropped[i] {
+                remap[i] = Some(intersects.len());
+                intersects.push(*intersect);
+            }
+        }
+        self.intersects = intersects;
+        for room in self.rooms.iter_mut() {
+            room.remap_intersects(&remap);
+        }
+        self.unlinkable = self.unlinkable.iter().filter_map(|i| remap[*i]).collect();
+        self.pruned = self.pruned.iter().filter_map(|i| remap[*i]).collect();
+    }
 
+    /// The room in the mirrored position, if there is one
+    fn image(&self, room: usize, flip: &Flip) -> Option<usize> {
+        let (rect, kind) = (self.rooms[room].rect, self.rooms[room].kind);
+        self.rooms.iter().position(|r| r.kind == kind && flip.maps(&rect, &r.rect))
+    }
+
+    /// The wall in the mirrored position, if there is one
+    fn intersect_image(&self, i: usize, flip: &Flip) -> Option<usize> {
+        let (a, b) = self.intersects[i].rooms;
+        let (a, b) = (self.image(a, flip)?, self.image(b, flip)?);
+        self.intersects.iter().position(|x| x.rooms == (a.min(b), a.max(b)))
+    }
 
-                    // We now have an edge segment that covers the overlap
-                    intersects.push(intersect);
+    /// A door along with its reflections, each only once
+    fn orbit(&self, i: usize) -> Vec<usize> {
+        let mut orbit = vec![i];
+        for flip in self.flips.iter() {
+            match self.intersect_image(i, flip) {
+                Some(j) if !orbit.contains(&j) && self.intersects[j].portal.is_some() => orbit.push(j),
+                _ => {}
+            }
+        }
+        orbit
+    }
+
+    /// Links two rooms both ways, recording it in the trace
+    fn connect(&mut self, a: usize, b: usize, link_type: LinkType) {
+        self.rooms[a].link(b, link_type);
+        self.rooms[b].link(a, link_type);
+        self.trace.push(Step::Link { a, b, link_type });
+    }
+
+    fn disconnect(&mut self, a: usize, b: usize, reason: UnlinkReason) {
+        self.rooms[a].unlink(b);
+        self.rooms[b].unlink(a);
+        self.trace.push(Step::Unlink { a, b, reason });
+    }
+
+    fn add_corridor_portal(&mut self, position: Vec2<f32>, edge: Compass, width: f32, from: usize, to: usize, link_type: LinkType) {
+        self.connect(from, to, link_type);
+        self.portals.push(Portal::new(position, edge, width, (from.min(to), from.max(to))));
+    }
+
+    /// Turns whatever free space the corridors did not use into typed voids, and gives each a service hatch.
+    /// Hatches open onto the adjoining room with the longest shared wall, or failing that onto a void that already has one.
+    pub fn generate_voids(&mut self, humans: &HumanCfg) {
+        self.voids = self.free.drain(..)
+            .filter(|rect| rect.size().w * rect.size().h > 1e-3)
+            .map(|rect| Void { rect, kind: VoidKind::classify(&rect, humans) })
+            .collect();
+        self.hatches.clear();
+
+        let width = humans.width();
+        let mut reachable = vec![false; self.voids.len()];
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for v in 0..self.voids.len() {
+                if reachable[v] {
+                    continue;
+                }
+                let rect = self.voids[v].rect;
+                let rooms = self.rooms.iter().enumerate()
+                    .flat_map(|(i, room)| room.parts().iter().map(move |part| (HatchTarget::Room(i), *part)));
+                let voids = self.voids.iter().enumerate()
+                    .filter(|(i, _)| reachable[*i])
+                    .map(|(i, void)| (HatchTarget::Void(i), void.rect));
+
+                // Rooms are preferred, so only fall back to voids when no room adjoins this one
+                let mut best: Option<(HatchTarget, Line<f32>, Compass)> = None;
+                let mut longest = 0.;
+                for (target, other) in rooms.chain(voids) {
+                    if let (Some((HatchTarget::Room(_), _, _)), HatchTarget::Void(_)) = (best, target) {
+                        break;
+                    }
+                    if let Some((line, edge)) = shared_edge(&rect, &other) {
+                        let length = line.start.distance(line.end);
+                        if length >= width && length > longest {
+                            longest = length;
+                            best = Some((target, line, edge));
+                        }
+                    }
+                }
+
+                if let Some((target, line, edge)) = best {
+                    let position = (line.start + line.end) / 2.;
+                    self.hatches.push(Hatch { position, edge, width, void: v, target });
+                    reachable[v] = true;
+                    progress = true;
                 }
             }
+        }
+    }
+
+    /// Every service hatch opening into the given void
+    pub fn hatches_of(&self, void: usize) -> Vec<&Hatch> {
+        self.hatches.iter().filter(|h| h.void == void || h.target == HatchTarget::Void(void)).collect()
+    }
+
+    /// Every door leading into or out of the given room
+    pub fn portals_of(&self, room: usize) -> Vec<&Portal> {
+        self.portals.iter().filter(|p| p.connects(room)).collect()
+    }
 
-            // Take our cached value and apply
-            self.rooms[i].intersects = intersects;
+    /// Every place a path can cross between two linked rooms. Links without a door of their own are crossed halfway between the rooms' centres.
+    pub(crate) fn crossings(&self, costs: &PathCfg) -> Vec<Crossing> {
+        let mut crossings = Vec::new();
+        for (a, room) in self.rooms.iter().enumerate() {
+            for link in room.links() {
+                let b = link.target;
+                if b < a {
+                    continue;
+                }
+                let weight = costs.cost(link.link_type);
+                let mut doors = self.portals.iter().filter(|p| p.rooms == (a, b)).peekable();
+                if doors.peek().is_none() {
+                    let midpoint = (room.centre() + self.rooms[b].centre()) / 2.;
+                    crossings.push(Crossing { rooms: [a, b], positions: [midpoint; 2], weight, climb: 0. });
+                }
+                for door in doors {
+                    crossings.push(Crossing { rooms: [a, b], positions: [door.position; 2], weight, climb: 0. });
+                }
+            }
         }
+        crossings
+    }
+
+    /// The cheapest walk from the centre of one room to the centre of another, passing through doors rather than room centres
+    pub fn find_path(&self, from: usize, to: usize, costs: &PathCfg) -> Option<Path> {
+        let goal = self.rooms[to].centre();
+        // The last stretch is walked at no extra cost, so it bounds the estimate as well
+        let cheapest = costs.cheapest().min(1.);
+        let estimate = |_, position: Vec2<f32>| position.distance(goal) * cheapest;
+        path::find(&self.crossings(costs), self.rooms.len(), (from, self.rooms[from].centre()), (to, goal), estimate)
+    }
+
+    /// Where an agent the width of a human can walk, see `NavMesh`
+    pub fn navmesh(&self, humans: &HumanCfg) -> NavMesh {
+        NavMesh::new(&self.rooms, &self.portals, &self.ports, &self.bounds, humans.width() / 2.)
     }
 
     pub fn export(&self) {
@@ -351,21 +918,21 @@ impl ModuleFactory {
             let red: u8 = lerpc(0., 255., 1. - room.value) as u8;
             let green: u8 = lerpc(0., 255., room.value) as u8;
 
-            room.draw(&mut img, Rgb::new(red,green,0));
+            match room.kind {
+                RoomKind::Room => room.draw(&mut img, Rgb::new(red,green,0)),
+                RoomKind::Corridor => room.draw(&mut img, Rgb::new(128,128,128)),
+            }
 
             for j in room.links() {
                 if j.target > i {
                     // Only draws the line if target is bigger index. Prevents doubles
-                    let a = self.rooms[i].rect;
-                    let b = self.rooms[j.target].rect;
-
                     let line = Line {
-                        start: a.center() * IMG_SCALE as f32,
-                        end: b.center() * IMG_SCALE as f32,
+                        start: self.rooms[i].centre() * IMG_SCALE as f32,
+                        end: self.rooms[j.target].centre() * IMG_SCALE as f32,
                     };
 
                     let colour = match j.link_type {
-                        LinkType::Direct => Rgb::new(0,255,0),
+                        LinkType::Direct(_) => Rgb::new(0,255,0),
                         LinkType::Bridge => Rgb::new(0,255,255),
                         LinkType::Tunnel => Rgb::new(255,255,0),
                         LinkType::Main => Rgb::new(82,56,255),
@@ -376,38 +943,75 @@ impl ModuleFactory {
             }
         }
 
+        for void in self.voids.iter() {
+            BoundingRect {
+                min: void.rect.min * IMG_SCALE as f32,
+                max: void.rect.max * IMG_SCALE as f32,
+            }.made_valid().into_rect().draw(&mut img, void.kind.colour());
+        }
+
+        for hatch in self.hatches.iter() {
+            let (start, end) = hatch.span();
+            Line {
+                start: start * IMG_SCALE as f32,
+                end: end * IMG_SCALE as f32,
+            }.draw(&mut img, Rgb::new(255,128,0));
+        }
+
+        for portal in self.portals.iter() {
+            let (start, end) = portal.span();
+            Line {
+                start: start * IMG_SCALE as f32,
+                end: end * IMG_SCALE as f32,
+            }.draw(&mut img, Rgb::new(0,0,255));
+        }
+
+        for port in self.ports.iter() {
+            let half = Vec2::new(port.boundary.width / 2., 0.);
+            Line {
+                start: (port.position - half) * IMG_SCALE as f32,
+                end: (port.position + half) * IMG_SCALE as f32,
+            }.draw(&mut img, Rgb::new(255,0,255));
+        }
+
         img.save("../bin/module_export.png").unwrap();
     }
 }
 
-fn find_adjacant_rooms(rooms: &Vec<Room>, index: usize) -> Vec<usize> {
+fn find_adjacant_rooms(rooms: &[Room], index: usize) -> Vec<usize> {
     let room = &rooms[index];
-    let rect = room.rect;
     let mut neighbors = Vec::new();
 
-    for (i, rhs) in rooms.iter().enumerate().map(|(i, rhs)| (i, rhs.rect)) {
+    // Compound rooms are tested part by part, so nothing in the corner an L leaves open counts as touching it
+    let touching = |rect: &BoundingRect<f32>, rhs: &BoundingRect<f32>| {
+        let x_overlap = rect.min.x <= rhs.max.x && rect.max.x >= rhs.min.x;
+        let y_overlap = rect.min.y <= rhs.max.y && rect.max.y >= rhs.min.y;
+        x_overlap && y_overlap
+    };
+    for (i, rhs) in rooms.iter().enumerate() {
         if i == index {
             continue;
         }
-        let x_overlap = (rect.min.x >= rhs.min.x && rect.min.x <= rhs.max.x) || (rect.max.x >= rhs.min.x && rect.max.x <= rhs.max.x);
-        let y_overlap = (rect.min.y >= rhs.min.y && rect.min.y <= rhs.max.y) || (rect.max.y >= rhs.min.y && rect.max.y <= rhs.max.y);
-        if x_overlap && y_overlap {
+        if room.parts().iter().any(|rect| rhs.parts().iter().any(|part| touching(rect, part))) {
             neighbors.push(i);
         }
     }
     neighbors
 }
 
-fn find_nearest_room(rooms: &Vec<Room>, index: usize) -> usize {
-    let rect = &rooms[index].rect;
+fn find_nearest_room(rooms: &[Room], index: usize) -> usize {
+    let room = &rooms[index];
     let mut nearest = index;
     let mut nearest_distance = f32::MAX;
 
-    for (i, rhs) in rooms.iter().enumerate().map(|(i, rhs)| (i, rhs.rect)) {
+    for (i, rhs) in rooms.iter().enumerate() {
         if i == index {
             continue;
         }
-        let dist = rect.center().distance(rhs.center());
+        // Measured between the closest pair of parts, as the centre of a compound room may not be on its floor
+        let dist = room.parts().iter()
+            .flat_map(|rect| rhs.parts().iter().map(move |part| rect.center().distance(part.center())))
+            .fold(f32::MAX, f32::min);
         if dist < nearest_distance {
             nearest = i;
             nearest_distance = dist;
@@ -416,71 +1020,110 @@ fn find_nearest_room(rooms: &Vec<Room>, index: usize) -> usize {
     nearest
 }
 
-fn connect_rooms(rooms: &mut Vec<Room>, a: usize, b: usize, link_type: LinkType) {
-    rooms[a].link(b, link_type);
-    rooms[b].link(a, link_type);
-}
-
-fn recursively_collect_islands(rooms: &Vec<Room>, islands: &mut Vec<Vec<usize>>, room_index: usize, depth: usize) {
-    let room = &rooms[room_index];
-    let mut island_index: Option<usize> = None;
-
-    println!("Running Recursive collect on room {} (depth {})...", room_index, depth);
-
-    // Check to see if we have an island
-    for (i, island) in islands.iter().enumerate() {
-        if island.contains(&room_index) {
-            // All good, carry on
-            island_index = Some(i);
-            println!("    found island index {}...", i);
-            break;
+/// The longest wall shared by any part of one room with any part of the other, see `shared_edge`
+pub(crate) fn shared_wall(a: &Room, b: &Room) -> Option<(Line<f32>, Compass)> {
+    let mut longest: Option<(Line<f32>, Compass)> = None;
+    for pa in a.parts() {
+        for pb in b.parts() {
+            match (shared_edge(pa, pb), longest) {
+                (Some((wall, _)), Some((best, _))) if wall.start.distance(wall.end) <= best.start.distance(best.end) => {}
+                (Some(wall), _) => longest = Some(wall),
+                _ => {}
+            }
         }
     }
+    longest
+}
 
-    if island_index.is_none() {
-        // We need to initiate an island
-        println!("    no island found- made island index {}...", islands.len());
-        island_index = Some(islands.len());
-        islands.push(vec!(room_index));
+/// True if the doorway lies wholly along one of the rect's walls
+fn on_wall(rect: &BoundingRect<f32>, portal: &Portal) -> bool {
+    let (start, end) = portal.span();
+    let near = |a: f32, b: f32| (a - b).abs() < 1e-4;
+    let within = |v: f32, min: f32, max: f32| v >= min - 1e-4 && v <= max + 1e-4;
+    if near(start.y, end.y) {
+        (near(start.y, rect.min.y) || near(start.y, rect.max.y)) && within(start.x, rect.min.x, rect.max.x) && within(end.x, rect.min.x, rect.max.x)
+    } else {
+        (near(start.x, rect.min.x) || near(start.x, rect.max.x)) && within(start.y, rect.min.y, rect.max.y) && within(end.y, rect.min.y, rect.max.y)
     }
+}
 
-    let i = island_index.unwrap(); // Will allwways work as we ensure it is set
-    
-    // We are already in the island- add our connected too
-    let mut room_q = Vec::new();
-
-    for c in room.connected().iter() {
-        println!("    found connected room {}", c);
-        if !islands[i].contains(c) {
-            // This room has not been seen before- recursively add it!
-            islands[i].push(*c);
-            room_q.push(*c);
-        }
+/// The stretch of an axis aligned wall running along a side of the given rect, if the rect lies against it at all
+fn along(line: &Line<f32>, rect: &BoundingRect<f32>) -> Option<Line<f32>> {
+    let within = |v: f32, min: f32, max: f32| v >= min - 1e-4 && v <= max + 1e-4;
+    let against = if (line.start.y - line.end.y).abs() < 1e-4 {
+        within(line.start.y, rect.min.y, rect.max.y)
+    } else {
+        within(line.start.x, rect.min.x, rect.max.x)
+    };
+    let clip = |p: Vec2<f32>| Vec2::partial_min(Vec2::partial_max(p, rect.min), rect.max);
+    let (start, end) = (clip(line.start), clip(line.end));
+    if against && start.distance(end) > 1e-4 {
+        Some(Line { start, end })
+    } else {
+        None
     }
+}
 
-    // Once we have added all the unseen connected rooms to our islands, run through them recursively
-    for r in room_q.iter() {
-        recursively_collect_islands(&rooms, islands, *r, depth + 1);
-    }
+/// True if the wall two rects share, if any, runs the whole length of a side of one of them. Parts of a room meeting
+/// this way make an L or a T, rather than a Z whose floor is only joined along part of a wall.
+fn flush(a: &BoundingRect<f32>, b: &BoundingRect<f32>) -> bool {
+    let (line, edge) = match shared_edge(a, b) {
+        Some(shared) => shared,
+        None => return true,
+    };
+    let length = line.start.distance(line.end);
+    let side = |r: &BoundingRect<f32>| if edge == Compass::East || edge == Compass::West { r.size().h } else { r.size().w };
+    (length - side(a)).abs() < 1e-4 || (length - side(b)).abs() < 1e-4
+}
 
-    // Once we reach this point, we need to find a new island entierly
-    if depth != 0 {
-        return;
-    }
+/// True if two pairs of rooms are the same, whichever way round
+fn same_pair(a: &(BoundingRect<f32>, BoundingRect<f32>), b: &(BoundingRect<f32>, BoundingRect<f32>)) -> bool {
+    let same = |x: &BoundingRect<f32>, y: &BoundingRect<f32>| x.min.distance(y.min) < 1e-4 && x.max.distance(y.max) < 1e-4;
+    (same(&a.0, &b.0) && same(&a.1, &b.1)) || (same(&a.0, &b.1) && same(&a.1, &b.0))
+}
+
+/// Finds the wall segment shared by two rooms, along with which edge of `a` it lies on.
+/// Returns None if the rooms are not adjacent, or only meet at a corner.
+pub(crate) fn shared_edge(a: &BoundingRect<f32>, b: &BoundingRect<f32>) -> Option<(Line<f32>, Compass)> {
+    let edge = if a.min.x == b.max.x {
+        Compass::West
+    } else if a.max.x == b.min.x {
+        Compass::East
+    } else if a.max.y == b.min.y {
+        Compass::North
+    } else if a.min.y == b.max.y {
+        Compass::South
+    } else {
+        return None;
+    };
 
-    for r in room_index + 1 .. rooms.len() {
-        let mut has_island = false;
-        for island in islands.iter() {
-            if island.contains(&r) {
-                has_island = true;
-                break;
+    let line = match edge {
+        Compass::West | Compass::East => {
+            let x = if edge == Compass::East { a.max.x } else { a.min.x };
+            let y_min = a.min.y.max(b.min.y);
+            let y_max = a.max.y.min(b.max.y);
+            if y_max <= y_min {
+                return None;
+            }
+            Line {
+                start: Vec2::new(x, y_min),
+                end: Vec2::new(x, y_max),
             }
         }
-
-        if !has_island {
-            recursively_collect_islands(&rooms, islands, r, depth + 1);
+        _ => {
+            let y = if edge == Compass::North { a.max.y } else { a.min.y };
+            let x_min = a.min.x.max(b.min.x);
+            let x_max = a.max.x.min(b.max.x);
+            if x_max <= x_min {
+                return None;
+            }
+            Line {
+                start: Vec2::new(x_min, y),
+                end: Vec2::new(x_max, y),
+            }
         }
-    }
+    };
+    Some((line, edge))
 }
 
 #[test]
@@ -494,13 +1137,669 @@ fn module_factory_test() {
         divide_disparity_chance: 0.4,
         split_offset: 0.4,
         split_degredation: 0.98,
+        ..ModuleCfg::default()
     };
 
     let mut module = ModuleFactory::new(cfg);
     module.link_rooms(true);
+    module.generate_portals(&HumanCfg::default());
     module.generate_islands();
     module.link_islands();
+    module.assert_connected();
+    module.generate_corridors(&HumanCfg::default());
     module.calculate_statistics();
-    module.generate_portals();
     module.export();
-}
\ No newline at end of file
+    module.assert_connected();
+
+    for room in module.rooms.iter() {
+        assert!(room.value >= 0. && room.value <= 1.);
+        assert!(room.stats.depth.is_some());
+        assert_eq!(room.stats.dead_end, room.stats.junctions == 1);
+    }
+
+    for (i, room) in module.rooms.iter().enumerate() {
+        for link in room.links() {
+            if let LinkType::Direct(r) = link.link_type {
+                let intersect = module.intersects[r.index];
+                assert_eq!(intersect.other(i), link.target);
+                assert!(intersect.length() > 0.);
+                // Both sides of the link share the intersect, from opposite edges
+                let back = module.rooms[link.target].link_type(i);
+                assert_eq!(back, Some(LinkType::Direct(IntersectRef { index: r.index, edge: r.edge.opposite() })));
+
+                // Every remaining direct link has a door that fits inside its wall
+                let portal = module.portals[intersect.portal.unwrap()];
+                let (start, end) = portal.span();
+                assert!(portal.connects(i) && portal.connects(link.target));
+                assert!(intersect.line.start.distance(start) >= 0.2 - 1e-4);
+                assert!(intersect.line.end.distance(end) >= 0.2 - 1e-4);
+            }
+        }
+    }
+
+    assert_walls(&module);
+    for i in module.unlinkable.iter() {
+        let intersect = module.intersects[*i];
+        assert!(intersect.portal.is_none());
+        let direct = LinkType::Direct(IntersectRef { index: *i, edge: intersect.edge });
+        assert_ne!(module.rooms[intersect.rooms.0].link_type(intersect.rooms.1), Some(direct));
+    }
+}
+
+#[test]
+fn shared_edge_test() {
+    let a = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(4., 4.));
+    let b = BoundingRect::new(Vec2::new(4., 2.), Vec2::new(8., 8.));
+    let corner = BoundingRect::new(Vec2::new(4., 4.), Vec2::new(8., 8.));
+
+    let (line, edge) = shared_edge(&a, &b).unwrap();
+    assert_eq!(edge, Compass::East);
+    assert_eq!(line.start, Vec2::new(4., 2.));
+    assert_eq!(line.end, Vec2::new(4., 4.));
+    assert_eq!(shared_edge(&b, &a).unwrap().1, Compass::West);
+    assert!(shared_edge(&a, &corner).is_none());
+}
+
+/// A module holding exactly the given rooms and free space, without running any layout
+#[cfg(test)]
+fn fixture(rooms: Vec<Room>, free: Vec<BoundingRect<f32>>) -> ModuleFactory {
+    let mut bounds = rooms[0].rect;
+    for rect in rooms.iter().map(|r| &r.rect).chain(free.iter()) {
+        bounds = BoundingRect::new(Vec2::partial_min(bounds.min, rect.min), Vec2::partial_max(bounds.max, rect.max));
+    }
+    ModuleFactory {
+        bounds,
+        rooms,
+        intersects: Vec::new(),
+        islands: Vec::new(),
+        portals: Vec::new(),
+        unlinkable: Vec::new(),
+        pruned: Vec::new(),
+        free,
+        unroutable: Vec::new(),
+        voids: Vec::new(),
+        hatches: Vec::new(),
+        ports: Vec::new(),
+        rejected: 0,
+        entrance: 0,
+        spines: Vec::new(),
+        colouring: Metric::Betweenness,
+        trace: Trace::new(),
+        flips: Vec::new(),
+        config: ModuleCfg::default(),
+        most_junctions: 0,
+    }
+}
+
+/// A module laid out from the given config, with a port in the middle of each end, as it would be within a wing
+#[cfg(test)]
+fn through(cfg: ModuleCfg, humans: &HumanCfg) -> ModuleFactory {
+    let port = |edge: Compass| BoundaryPort { edge, offset: 0., width: humans.width(), neighbour: super::ModuleAddress::new(0, 0) };
+    ModuleFactory::with_ports(cfg, humans, &[port(Compass::South), port(Compass::North)])
+}
+
+/// Panics if any two of the module's room parts, free rects and voids overlap
+#[cfg(test)]
+fn assert_no_overlap(module: &ModuleFactory) {
+    let rects: Vec<BoundingRect<f32>> = module.rooms.iter()
+        .flat_map(|r| r.parts().iter().copied())
+        .chain(module.free.iter().copied())
+        .chain(module.voids.iter().map(|v| v.rect))
+        .collect();
+    for (i, a) in rects.iter().enumerate() {
+        for b in rects[i + 1..].iter() {
+            let overlap_x = a.max.x.min(b.max.x) - a.min.x.max(b.min.x);
+            let overlap_y = a.max.y.min(b.max.y) - a.min.y.max(b.min.y);
+            assert!(overlap_x <= 1e-3 || overlap_y <= 1e-3, "{:?} overlaps {:?}", a, b);
+        }
+    }
+}
+
+/// Panics unless every wall lies between the rooms it names, and every direct link goes through a wall onto its target
+#[cfg(test)]
+fn assert_walls(module: &ModuleFactory) {
+    for (i, intersect) in module.intersects.iter().enumerate() {
+        let (a, b) = intersect.rooms;
+        let lies_along = |room: usize| module.rooms[room].parts().iter().any(|part| {
+            along(&intersect.line, part).map_or(0., |line| line.start.distance(line.end)) > intersect.length() - 1e-3
+        });
+        assert!(a < b && lies_along(a) && lies_along(b), "wall {} is not between rooms {} and {}", i, a, b);
+    }
+    for (i, room) in module.rooms.iter().enumerate() {
+        for link in room.links() {
+            if let LinkType::Direct(r) = link.link_type {
+                let intersect = module.intersects[r.index];
+                assert_eq!(intersect.other(i), link.target);
+                assert_eq!(r.edge, if intersect.rooms.0 == i { intersect.edge } else { intersect.edge.opposite() });
+            }
+        }
+    }
+    assert!(module.unlinkable.iter().chain(module.pruned.iter()).all(|i| *i < module.intersects.len()));
+}
+
+#[test]
+fn compound_adjacency_test() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32| BoundingRect::new(Vec2::new(x0, y0), Vec2::new(x1, y1));
+    let mut l = Room::new(rect(0., 0., 2., 6.));
+    l.absorb(&Room::new(rect(2., 0., 5., 2.)));
+    // The second room sits in the corner the L leaves open, clear of both its parts
+    let rooms = vec![l, Room::new(rect(3., 3., 5., 6.)), Room::new(rect(6., 3., 8., 6.))];
+
+    assert!(find_adjacant_rooms(&rooms, 0).is_empty());
+    assert!(find_adjacant_rooms(&rooms, 1).is_empty());
+    assert!(shared_wall(&rooms[0], &rooms[1]).is_none());
+    // The L's bounding box is closer, but its floor is not
+    assert_eq!(find_nearest_room(&rooms, 1), 2);
+}
+
+#[test]
+fn merge_order_test() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32| BoundingRect::new(Vec2::new(x0, y0), Vec2::new(x1, y1));
+    // Two apart copies of a tall room with two smaller ones along its east side. Whichever of those the tall room takes
+    // first, the other then joins it from further down the list, which must not skip the next copy's tall room.
+    let gadget = |x: f32| vec![
+        Room::new(rect(x, 0., x + 2., 4.)),
+        Room::new(rect(x + 2., 0., x + 4., 2.)),
+        Room::new(rect(x + 2., 2., x + 3., 4.)),
+    ];
+    let mut module = fixture(gadget(0.).into_iter().chain(gadget(10.)).collect(), Vec::new());
+    module.config = ModuleCfg { merge_rate: 1., min_room_width: 1., min_room_depth: 1., ..ModuleCfg::default() };
+    module.merge_rooms();
+
+    assert_eq!(module.rooms.len(), 2);
+    assert!(module.rooms.iter().all(|r| r.parts().len() == 3));
+}
+
+#[test]
+fn portal_clearance_test() {
+    let mut module = fixture(vec![
+        Room::new(BoundingRect::new(Vec2::new(0., 0.), Vec2::new(4., 4.))),
+        Room::new(BoundingRect::new(Vec2::new(4., 3.), Vec2::new(8., 8.))),
+        Room::new(BoundingRect::new(Vec2::new(0., 4.), Vec2::new(4., 8.))),
+    ], Vec::new());
+    module.link_rooms(false);
+    module.generate_portals(&HumanCfg::default());
+
+    // 0 and 1 only share a metre of wall, which cannot fit a door and its frame
+    assert_eq!(module.unlinkable.len(), 1);
+    assert!(!module.rooms[0].is_linked(1));
+    assert!(module.rooms[0].is_linked(2));
+    assert_eq!(module.portals_of(0).len(), 1);
+    assert_eq!(module.portals_of(2).len(), 2);
+}
+
+#[test]
+fn corridor_test() {
+    let mut module = fixture(vec![
+        Room::new(BoundingRect::new(Vec2::new(0., 0.), Vec2::new(4., 4.))),
+        Room::new(BoundingRect::new(Vec2::new(12., 6.), Vec2::new(16., 10.))),
+    ], vec![
+        BoundingRect::new(Vec2::new(4., 0.), Vec2::new(16., 6.)),
+        BoundingRect::new(Vec2::new(0., 4.), Vec2::new(4., 10.)),
+        BoundingRect::new(Vec2::new(4., 6.), Vec2::new(12., 10.)),
+    ]);
+    module.link_rooms(true);
+    assert_eq!(module.rooms[0].link_type(1), Some(LinkType::Tunnel));
+    module.generate_corridors(&HumanCfg::default());
+
+    assert!(module.unroutable.is_empty());
+    assert!(!module.rooms[0].is_linked(1));
+    assert!(module.rooms.len() > 2);
+
+    // Walk the corridor from one room to the other
+    let mut previous = 0;
+    let mut current = module.rooms[0].connected()[0];
+    while current != 1 {
+        assert_eq!(module.rooms[current].kind, RoomKind::Corridor);
+        let next: Vec<usize> = module.rooms[current].connected().into_iter().filter(|r| *r != previous).collect();
+        assert_eq!(next.len(), 1);
+        previous = current;
+        current = next[0];
+    }
+    assert_eq!(module.portals.len(), module.rooms.len() - 1);
+
+    // Corridors are as wide as a human plus their frame, and never overlap the space left over
+    for room in module.rooms[2..].iter() {
+        assert!(room.size().w.min(room.size().h) >= 1.6 - 1e-4);
+    }
+    assert_no_overlap(&module);
+}
+
+#[test]
+fn corridor_cut_test() {
+    // The room in the middle fills the gap between the other two, so the corridor has to be cut through it
+    let mut module = fixture(vec![
+        Room::new(BoundingRect::new(Vec2::new(0., 0.), Vec2::new(4., 4.))),
+        Room::new(BoundingRect::new(Vec2::new(12., 0.), Vec2::new(16., 4.))),
+        Room::new(BoundingRect::new(Vec2::new(4., 0.), Vec2::new(12., 10.))),
+    ], Vec::new());
+    module.connect(0, 1, LinkType::Tunnel);
+    module.generate_corridors(&HumanCfg::default());
+    assert!(module.unroutable.is_empty());
+
+    // The room keeps the space to one side of the corridor, and opens onto it
+    let room = &module.rooms[2];
+    assert_eq!(room.kind, RoomKind::Room);
+    assert!(room.rect.min.y > 0. && room.rect.max == Vec2::new(12., 10.));
+    let corridors: Vec<usize> = (3..module.rooms.len()).collect();
+    assert!(!corridors.is_empty());
+    assert!(corridors.iter().all(|i| module.rooms[*i].kind == RoomKind::Corridor));
+    assert!(room.connected().iter().any(|i| corridors.contains(i)));
+
+    // The ends of the link are joined through the corridor, and not through the room
+    assert!(!module.rooms[0].is_linked(2) && !module.rooms[1].is_linked(2));
+    let path = module.find_path(0, 1, &PathCfg::default()).unwrap();
+    assert!(!path.rooms.contains(&2));
+
+    // Doors through a wall are all one human wide; only the open seams between corridor pieces are wider
+    let humans = HumanCfg::default();
+    for portal in module.portals.iter() {
+        let seam = corridors.contains(&portal.rooms.0) && corridors.contains(&portal.rooms.1);
+        let width = if seam { module.config.door_clearance(&humans) } else { humans.width() };
+        assert_eq!(portal.width, width);
+    }
+
+    // The sliver too thin for a room is left free, and nothing overlaps
+    assert!(!module.free.is_empty());
+    assert_no_overlap(&module);
+    let area: f32 = module.rooms.iter().map(|r| r.area()).chain(module.free.iter().map(|r| r.size().w * r.size().h)).sum();
+    assert!((area - (16. + 16. + 80.)).abs() < 1e-3);
+    module.generate_islands();
+    module.assert_connected();
+}
+
+#[test]
+fn corridor_walls_test() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32| BoundingRect::new(Vec2::new(x0, y0), Vec2::new(x1, y1));
+    // The corridor is cut across the middle room, leaving a strip too thin for a room along its north wall
+    let mut module = fixture(vec![
+        Room::new(rect(0., 0., 4., 8.)),
+        Room::new(rect(12., 0., 16., 8.)),
+        Room::new(rect(4., 0., 12., 6.)),
+        Room::new(rect(4., -4., 9., 0.)),
+        Room::new(rect(9., -1., 10., 0.)),
+        Room::new(rect(6., 6., 7., 8.)),
+    ], Vec::new());
+    module.config.seed = 4;
+    let humans = HumanCfg::default();
+    module.link_rooms(false);
+    module.generate_portals(&humans);
+    assert_eq!(module.unlinkable.len(), 3);
+    module.connect(0, 1, LinkType::Tunnel);
+    module.generate_corridors(&humans);
+    assert_walls(&module);
+
+    assert_eq!((module.rooms[2].kind, module.rooms[2].rect), (RoomKind::Room, rect(4., 0., 12., 3.2)));
+    assert_eq!(module.free, vec![rect(4., 4.8, 12., 6.)]);
+
+    // The west door now opens onto the corridor, and its wall only runs as far as the corridor does
+    let corridor = module.portals[0].rooms.1;
+    assert_eq!(module.rooms[corridor].kind, RoomKind::Corridor);
+    let wall = module.intersects.iter().find(|i| i.portal == Some(0)).unwrap();
+    assert_eq!(wall.rooms, (0, corridor));
+    assert_eq!((wall.line.start, wall.line.end), (Vec2::new(4., 3.2), Vec2::new(4., 4.8)));
+
+    // The short wall onto the room to the north now only faces free space, so it is gone
+    assert!(module.intersects.iter().all(|i| i.rooms.0 != 5 && i.rooms.1 != 5));
+    assert_eq!(module.unlinkable.len(), 2);
+}
+
+#[test]
+fn islands_test() {
+    let mut module = fixture((0..6).map(|i| {
+        let x = (i * 10) as f32;
+        Room::new(BoundingRect::new(Vec2::new(x, 0.), Vec2::new(x + 4., 4.)))
+    }).collect(), Vec::new());
+    module.connect(5, 1, LinkType::Tunnel);
+    module.connect(3, 2, LinkType::Tunnel);
+
+    module.generate_islands();
+    assert_eq!(module.islands, vec![vec![0], vec![1, 5], vec![2, 3], vec![4]]);
+    assert!(!module.is_connected());
+
+    module.link_islands();
+    assert_eq!(module.islands.len(), 1);
+    module.assert_connected();
+}
+
+#[test]
+fn loop_factor_test() {
+    let generate = |loop_factor: f32| {
+        let cfg = ModuleCfg {
+            seed: 2741,
+            loop_factor,
+            ..ModuleCfg::default()
+        };
+        let mut module = ModuleFactory::new(cfg);
+        module.link_rooms(true);
+        module.generate_portals(&HumanCfg::default());
+        let cyclomatic = module.link_spanning_tree();
+        assert_eq!(cyclomatic, module.cyclomatic_number());
+        (module, cyclomatic)
+    };
+
+    let (tree, none) = generate(0.);
+    let (half, some) = generate(0.5);
+    let (full, all) = generate(1.);
+    assert_eq!(none, 0);
+    assert!(full.pruned.is_empty());
+    assert!(none <= some && some <= all);
+    assert!(all > 0);
+
+    // Every pruned door has gone, and every remaining one is still referenced by its intersect
+    for module in [tree, half].iter() {
+        assert_eq!(module.portals.len(), module.intersects.iter().filter(|i| i.portal.is_some()).count());
+        for i in module.pruned.iter() {
+            let (a, b) = module.intersects[*i].rooms;
+            assert!(!module.rooms[a].is_linked(b));
+            assert!(module.intersects[*i].portal.is_none());
+        }
+    }
+}
+
+#[test]
+fn layout_strategies_test() {
+    use crate::config::LayoutMode;
+    for mode in [LayoutMode::Bsp, LayoutMode::Treemap, LayoutMode::Grid, LayoutMode::Spine].iter() {
+        let cfg = ModuleCfg {
+            seed: 5119,
+            layout: *mode,
+            ..ModuleCfg::default()
+        };
+        let module = ModuleFactory::default(cfg, &HumanCfg::default());
+        module.assert_connected();
+
+        // Rooms never overlap, and together with the voids they cover the whole module
+        assert_no_overlap(&module);
+        let area: f32 = module.rooms.iter()
+            .map(|r| r.area())
+            .chain(module.voids.iter().map(|v| v.rect.size().w * v.rect.size().h))
+            .sum();
+        let bounds = module.bounds.size();
+        assert!((area - bounds.w * bounds.h).abs() < 1., "{:?} covers {} of {}", mode, area, bounds.w * bounds.h);
+    }
+}
+
+#[test]
+fn spine_layout_test() {
+    use crate::config::{LayoutMode, Mirror};
+    let humans = HumanCfg::default();
+    // Mirrored across the module, the spine is laid out in the west half and reflected into the east
+    for (mirror, count) in [(Mirror::None, 1), (Mirror::Lateral, 2)].iter() {
+        let cfg = ModuleCfg {
+            seed: 887,
+            layout: LayoutMode::Spine,
+            mirror: *mirror,
+            spine_offset: -4.,
+            loop_factor: 0.,
+            ..ModuleCfg::default()
+        };
+        let module = through(cfg, &humans);
+        module.assert_connected();
+        assert_eq!(module.spines.len(), *count);
+
+        // Every room has a door straight onto a spine, even with every loop pruned
+        for spine in module.spines.iter() {
+            assert_eq!(module.rooms[*spine].kind, RoomKind::Corridor);
+        }
+        for (i, room) in module.rooms.iter().enumerate() {
+            if room.kind == RoomKind::Room {
+                let onto_spine = module.portals_of(i).iter().any(|p| module.spines.iter().any(|s| p.rooms == (i, *s)));
+                assert!(onto_spine, "{:?} room {} has no door onto a spine", mirror, i);
+            }
+        }
+
+        // Each spine runs between the corridors holding the ports at either end
+        for (spine, port) in module.spines.iter().zip(module.ports.iter()) {
+            assert!(module.rooms[*spine].is_linked(port.room));
+        }
+    }
+}
+
+#[test]
+fn mirror_test() {
+    use crate::config::Mirror;
+    for mode in [Mirror::Lateral, Mirror::Length, Mirror::Both].iter() {
+        let cfg = ModuleCfg {
+            seed: 3307,
+            mirror: *mode,
+            ..ModuleCfg::default()
+        };
+        let module = ModuleFactory::default(cfg, &HumanCfg::default());
+        module.assert_connected();
+        assert!(!module.flips.is_empty());
+
+        // Every room, door and link has a twin in each mirrored position
+        let plain: Vec<usize> = (0..module.rooms.len()).filter(|i| module.rooms[*i].kind == RoomKind::Room).collect();
+        for flip in module.flips.iter() {
+            for i in plain.iter() {
+                let twin = module.image(*i, flip).unwrap_or_else(|| panic!("{:?} room {} has no twin", mode, i));
+                for link in module.rooms[*i].links().iter().filter(|l| matches!(l.link_type, LinkType::Direct(_))) {
+                    assert!(module.rooms[twin].is_linked(module.image(link.target, flip).unwrap()));
+                }
+            }
+            for portal in module.portals.iter().filter(|p| plain.contains(&p.rooms.0) && plain.contains(&p.rooms.1)) {
+                let image = flip.point(portal.position);
+                assert!(module.portals.iter().any(|p| p.position.distance(image) < 1e-3), "{:?} door has no twin", mode);
+            }
+        }
+
+        // Rooms meeting their reflection at a seam are merged into one across it
+        let centre = module.bounds.center();
+        assert!(plain.iter().flat_map(|i| module.rooms[*i].parts().iter()).any(|rect| {
+            rect.min.x < centre.x && rect.max.x > centre.x || rect.min.y < centre.y && rect.max.y > centre.y
+        }));
+
+        // The trace replays the reflection
+        let end = module.trace.replay().last().unwrap();
+        for (a, b) in end.rooms.iter().zip(module.rooms.iter()) {
+            assert_eq!((a.rect, a.kind), (b.rect, b.kind));
+        }
+    }
+}
+
+#[test]
+fn trace_test() {
+    use super::Pick;
+    let cfg = ModuleCfg {
+        seed: 6151,
+        ..ModuleCfg::default()
+    };
+    let module = ModuleFactory::default(cfg, &HumanCfg::default());
+    assert!(module.trace.steps.iter().any(|s| matches!(s, Step::Split { pick: Pick::Area, .. })));
+    assert!(module.trace.steps.iter().any(|s| matches!(s, Step::Unlink { reason: UnlinkReason::Routed, .. })));
+
+    // Replaying every step rebuilds the module, one decision at a time
+    let mut replay = module.trace.replay();
+    let mut steps = 0;
+    while replay.step().is_some() {
+        steps += 1;
+    }
+    assert_eq!(steps, module.trace.len());
+    let end = replay.current();
+    assert_eq!(end.rooms.len(), module.rooms.len());
+    for (a, b) in end.rooms.iter().zip(module.rooms.iter()) {
+        assert_eq!((a.rect, a.kind), (b.rect, b.kind));
+        assert_eq!(a.links(), b.links());
+    }
+    let free: Vec<BoundingRect<f32>> = end.free.iter().filter(|r| r.size().w * r.size().h > 1e-3).copied().collect();
+    let voids: Vec<BoundingRect<f32>> = module.voids.iter().map(|v| v.rect).collect();
+    assert_eq!(free, voids);
+
+    // BSP starts from the whole module as a single room
+    let counts: Vec<usize> = module.trace.replay().map(|s| s.rooms.len()).collect();
+    assert_eq!(counts[0], 1);
+}
+
+#[test]
+fn find_path_test() {
+    let cfg = ModuleCfg {
+        seed: 4421,
+        ..ModuleCfg::default()
+    };
+    let module = ModuleFactory::default(cfg, &HumanCfg::default());
+    let to = module.rooms.len() - 1;
+    let costs = PathCfg::default();
+    let path = module.find_path(0, to, &costs).unwrap();
+
+    assert_eq!((path.rooms[0], *path.rooms.last().unwrap()), (0, to));
+    for pair in path.rooms.windows(2) {
+        assert!(module.rooms[pair[0]].is_linked(pair[1]));
+    }
+    // The path passes through doors, not room centres
+    for (room, point) in path.points[1..path.points.len() - 1].iter() {
+        assert!(module.portals_of(*room).iter().any(|p| p.position == *point));
+    }
+    let length: f32 = path.points.windows(2).map(|w| w[0].1.distance(w[1].1)).sum();
+    assert!((path.cost - length).abs() < 1e-2);
+
+    // Pricier corridors can only make the cheapest path dearer
+    let dear: PathCfg = toml::from_str("tunnel = 10.0\nbridge = 10.0").unwrap();
+    assert!(module.find_path(0, to, &dear).unwrap().cost >= path.cost);
+}
+
+#[test]
+fn navmesh_test() {
+    use super::NavArea;
+    let humans = HumanCfg::default();
+    let cfg = ModuleCfg {
+        seed: 9173,
+        merge_rate: 0.5,
+        ..ModuleCfg::default()
+    };
+    let module = through(cfg, &humans);
+    assert!(module.rooms.iter().any(|r| r.is_compound()));
+    let mesh = module.navmesh(&humans);
+    assert_eq!(mesh.polys.iter().filter(|p| matches!(p.area, NavArea::Port(_))).count(), 2);
+    let radius = humans.width() / 2.;
+
+    for poly in mesh.polys.iter() {
+        match poly.area {
+            NavArea::Room(i) => {
+                // Floors keep the agent's radius clear of every wall, part by part
+                let parts = module.rooms[i].parts();
+                let inside = |p: Vec2<f32>| p.x > poly.rect.min.x && p.x < poly.rect.max.x && p.y > poly.rect.min.y && p.y < poly.rect.max.y;
+                let part = parts.iter().find(|p| inside(p.center())).unwrap();
+                let open = |side: Compass| parts.iter().any(|q| q != part && shared_edge(part, q).map(|(_, edge)| edge) == Some(side));
+                if !open(Compass::West) {
+                    assert!((poly.rect.min.x - part.min.x - radius).abs() < 1e-4);
+                }
+                if !open(Compass::North) {
+                    assert!((part.max.y - poly.rect.max.y - radius).abs() < 1e-4);
+                }
+            }
+            NavArea::Door(_) => assert_eq!(poly.links.len(), 2),
+            NavArea::Port(_) => assert_eq!(poly.links.len(), 1),
+        }
+        // Linked polygons really do meet along the shared edge
+        for link in poly.links.iter() {
+            let (start, end) = link.edge;
+            let other = &mesh.polys[link.target];
+            assert!(mesh.polys[link.target].links.iter().any(|l| l.edge == link.edge));
+            assert!(start.x == end.x || start.y == end.y);
+            assert!(start.x >= other.rect.min.x - 1e-4 && end.x <= other.rect.max.x + 1e-4);
+        }
+    }
+
+    // Every room reached through doors can be walked to
+    if module.unroutable.is_empty() {
+        let entrance = mesh.find_poly(module.rooms[module.entrance].centre()).unwrap();
+        assert!(mesh.reachable(entrance).iter().all(|r| *r));
+    }
+    assert_eq!(mesh.to_obj().lines().filter(|l| l.starts_with("v ")).count(), 4 * mesh.polys.len());
+}
+
+#[test]
+fn voids_test() {
+    let humans = HumanCfg::default();
+    let cfg = ModuleCfg {
+        seed: 1409,
+        ..ModuleCfg::default()
+    };
+    let module = ModuleFactory::default(cfg, &humans);
+    assert!(module.free.is_empty());
+    assert!(!module.voids.is_empty());
+    // Voids never overlap a room, corridors included
+    assert_no_overlap(&module);
+
+    for (v, void) in module.voids.iter().enumerate() {
+        assert_eq!(void.kind, VoidKind::classify(&void.rect, &humans));
+        for hatch in module.hatches.iter().filter(|h| h.void == v) {
+            let (start, end) = hatch.span();
+            let (line, _) = match hatch.target {
+                HatchTarget::Room(r) => shared_edge(&void.rect, &module.rooms[r].rect).unwrap(),
+                HatchTarget::Void(w) => shared_edge(&void.rect, &module.voids[w].rect).unwrap(),
+            };
+            // Hatches sit wholly within the wall they open through
+            for p in [start, end].iter() {
+                assert!(p.x >= line.start.x - 1e-4 && p.x <= line.end.x + 1e-4);
+                assert!(p.y >= line.start.y - 1e-4 && p.y <= line.end.y + 1e-4);
+            }
+        }
+    }
+
+    // Voids are not part of the room graph
+    module.assert_connected();
+    assert!(module.hatches.len() <= module.voids.len());
+}
+
+#[test]
+fn compound_rooms_test() {
+    use super::NavArea;
+    use crate::config::Mirror;
+    let humans = HumanCfg::default();
+    let mut most_parts = 0;
+    for seed in [1, 2, 3, 4, 5, 6, 7, 8, 73563].iter() {
+        let cfg = ModuleCfg { seed: *seed, merge_rate: 0.5, ..ModuleCfg::default() };
+        let module = through(cfg, &humans);
+        module.assert_connected();
+        assert_walls(&module);
+
+        let compound: Vec<&Room> = module.rooms.iter().filter(|r| r.is_compound()).collect();
+        assert!(!compound.is_empty(), "seed {} has no compound rooms", seed);
+        for room in compound.iter() {
+            // Never a plain rectangle, so at least an L, and never a Z
+            let bounds = room.size().w * room.size().h;
+            assert!(room.area() < bounds - 1e-3);
+            assert!(room.parts().len() <= MAX_PARTS);
+            assert!(room.outline().len() >= 6);
+            for (i, a) in room.parts().iter().enumerate() {
+                assert!(room.parts()[i + 1..].iter().all(|b| flush(a, b)), "seed {} has a stepped room", seed);
+            }
+            most_parts = most_parts.max(room.parts().len());
+        }
+
+        // Parts never overlap, and with the voids still cover the whole module
+        assert_no_overlap(&module);
+        let area: f32 = module.rooms.iter().map(|r| r.area()).chain(module.voids.iter().map(|v| v.rect.size().w * v.rect.size().h)).sum();
+        let bounds = module.bounds.size();
+        assert!((area - bounds.w * bounds.h).abs() < 1.);
+
+        // Every part of every room can be walked to
+        let mesh = module.navmesh(&humans);
+        let entrance = mesh.find_poly(module.rooms[module.entrance].centre()).unwrap();
+        assert!(mesh.reachable(entrance).iter().all(|r| *r), "seed {} has floor that cannot be walked to", seed);
+        for (i, room) in module.rooms.iter().enumerate().filter(|(_, r)| r.is_compound()) {
+            let floors = mesh.polys.iter().filter(|p| p.area == NavArea::Room(i)).count();
+            assert_eq!(floors, room.parts().len());
+        }
+
+        // The trace replays the merges
+        let end = module.trace.replay().last().unwrap();
+        for (a, b) in end.rooms.iter().zip(module.rooms.iter()) {
+            assert_eq!(a.parts(), b.parts());
+        }
+    }
+    // Rooms grow past an L without any mirroring
+    assert_eq!(most_parts, MAX_PARTS);
+
+    // Mirrored rooms are merged along with their images
+    let mirrored = ModuleFactory::default(ModuleCfg { seed: 2417, merge_rate: 0.5, mirror: Mirror::Lateral, ..ModuleCfg::default() }, &humans);
+    mirrored.assert_connected();
+    for flip in mirrored.flips.iter() {
+        for (i, room) in mirrored.rooms.iter().enumerate().filter(|(_, r)| r.is_compound()) {
+            let twin = mirrored.image(i, flip).unwrap();
+            assert_eq!(mirrored.rooms[twin].area(), room.area());
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt::Write;
+use prima::geom::BoundingRect;
+use vek::{Vec2, Vec3};
+use crate::geom::Compass;
+use super::{Room, Portal, Port};
+use super::module_factory::shared_edge;
+
+/// Doors are exactly one agent wide, which would close them entirely once shrunk. They are kept at least this wide,
+/// so the agent can pass along the doorway's centre line.
+const MIN_PASSAGE: f32 = 0.05;
+
+/// What a navmesh polygon covers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NavArea {
+    /// The floor of a room or corridor, by room index
+    Room(usize),
+    /// The opening through a wall, by portal index
+    Door(usize),
+    /// The opening through the end of the module into its neighbour, by port index
+    Port(usize),
+}
+
+/// The edge two polygons share, which an agent crosses to get from one to the other
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NavLink {
+    pub target: usize,
+    pub edge: (Vec2<f32>, Vec2<f32>),
+}
+
+/// A walkable area. All polygons are axis aligned rectangles in module space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavPoly {
+    pub rect: BoundingRect<f32>,
+    pub area: NavArea,
+    pub links: Vec<NavLink>,
+}
+
+impl NavPoly {
+    /// Corners, anticlockwise from the south west
+    pub fn vertices(&self) -> [Vec2<f32>; 4] {
+        let (min, max) = (self.rect.min, self.rect.max);
+        [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+    }
+}
+
+/// Where the centre of an agent of the given radius can stand. Room floors are shrunk away from their walls by the
+/// radius, and joined through their doors by polygons spanning the gap this leaves either side of the wall.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavMesh {
+    pub radius: f32,
+    pub polys: Vec<NavPoly>,
+}
+
+impl NavMesh {
+    pub(crate) fn new(rooms: &[Room], portals: &[Portal], ports: &[Port], bounds: &BoundingRect<f32>, radius: f32) -> Self {
+        let mut polys = Vec::new();
+        // The polygons covering each room, one per part of its floor large enough to hold the agent at all
+        let mut floors = vec![Vec::new(); rooms.len()];
+        for (i, room) in rooms.iter().enumerate() {
+            for rect in room.parts().iter().filter_map(|part| floor(room, part, radius)) {
+                floors[i].push(polys.len());
+                polys.push(NavPoly { rect, area: NavArea::Room(i), links: Vec::new() });
+            }
+            // Parts of the same room are walked between freely
+            for (k, a) in floors[i].iter().enumerate() {
+                for b in floors[i][k + 1..].iter() {
+                    if shared_edge(&polys[*a].rect, &polys[*b].rect).is_some() {
+                        join(&mut polys, *a, *b);
+                    }
+                }
+            }
+        }
+        // The polygon of the room's floor a doorway opens onto
+        let opening = |polys: &[NavPoly], room: usize, rect: &BoundingRect<f32>| {
+            floors[room].iter().copied().find(|f| shared_edge(&polys[*f].rect, rect).is_some())
+        };
+
+        for (i, portal) in portals.iter().enumerate() {
+            let rect = doorway(portal.position, portal.span(), radius);
+            let (a, b) = match (opening(&polys, portal.rooms.0, &rect), opening(&polys, portal.rooms.1, &rect)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            let door = polys.len();
+            polys.push(NavPoly { rect, area: NavArea::Door(i), links: Vec::new() });
+            join(&mut polys, door, a);
+            join(&mut polys, door, b);
+        }
+
+        // Ports open onto the module boundary, where the neighbouring module's navmesh picks up
+        for (i, port) in ports.iter().enumerate() {
+            let half = Vec2::new(port.boundary.width / 2., 0.);
+            let rect = doorway(port.position, (port.position - half, port.position + half), radius);
+            let floor = match opening(&polys, port.room, &rect) {
+                Some(floor) => floor,
+                None => continue,
+            };
+            let rect = BoundingRect::new(Vec2::partial_max(rect.min, bounds.min), Vec2::partial_min(rect.max, bounds.max));
+            let door = polys.len();
+            polys.push(NavPoly { rect, area: NavArea::Port(i), links: Vec::new() });
+            join(&mut polys, door, floor);
+        }
+
+        Self { radius, polys }
+    }
+
+    /// The polygon holding the given point, if it is walkable
+    pub fn find_poly(&self, point: Vec2<f32>) -> Option<usize> {
+        self.polys.iter().position(|p| {
+            point.x >= p.rect.min.x && point.x <= p.rect.max.x && point.y >= p.rect.min.y && point.y <= p.rect.max.y
+        })
+    }
+
+    /// The polygons that can be reached from the given one
+    pub fn reachable(&self, from: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.polys.len()];
+        let mut stack = vec![from];
+        reached[from] = true;
+        while let Some(p) = stack.pop() {
+            for link in self.polys[p].links.iter() {
+                if !reached[link.target] {
+                    reached[link.target] = true;
+                    stack.push(link.target);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Writes the mesh as a Wavefront OBJ, flat on the floor of the module with y up
+    pub fn to_obj(&self) -> String {
+        self.to_obj_with(f32::INFINITY, |p| Vec3::new(p.x as f64, 0., p.y as f64))
+    }
+
+    /// Writes the mesh as a Wavefront OBJ, placing each vertex with the given function. Polygons are cut into strips no
+    /// longer than `step` along the module, so they can follow the curve of the ring.
+    pub fn to_obj_with<F>(&self, step: f32, place: F) -> String where F: Fn(Vec2<f32>) -> Vec3<f64> {
+        let mut obj = String::new();
+        let mut faces = Vec::new();
+        let mut count = 0;
+        for poly in self.polys.iter() {
+            let (min, max) = (poly.rect.min, poly.rect.max);
+            let strips = ((max.y - min.y) / step).ceil().max(1.) as usize;
+            for i in 0..=strips {
+                let y = if i == strips { max.y } else { min.y + (max.y - min.y) * i as f32 / strips as f32 };
+                for x in [min.x, max.x].iter() {
+                    let v = place(Vec2::new(*x, y));
+                    writeln!(obj, "v {} {} {}", v.x, v.y, v.z).unwrap();
+                }
+            }
+            // Each strip joins the pair of vertices at its south edge to the pair at its north edge
+            for i in 0..strips {
+                let first = count + 2 * i + 1;
+                faces.push([first, first + 1, first + 3, first + 2]);
+            }
+            count += 2 * (strips + 1);
+        }
+        for [a, b, c, d] in faces {
+            writeln!(obj, "f {} {} {} {}", a, b, c, d).unwrap();
+        }
+        obj
+    }
+}
+
+/// Where the agent can stand on one part of a room's floor. Sides against a wall are pulled in by the radius. A side
+/// lying wholly against another part of the room is open floor, and reaches out by the radius to meet that part's
+/// polygon, unless the two parts meet wall to wall, in which case both stop at the seam. Parts only ever meet along the
+/// whole of one side, see `flush`, so every part of the room is joined to the next.
+fn floor(room: &Room, part: &BoundingRect<f32>, radius: f32) -> Option<BoundingRect<f32>> {
+    let covers = |other: &BoundingRect<f32>, side: Compass| -> bool {
+        match side {
+            Compass::West => other.max.x == part.min.x && other.min.y <= part.min.y && other.max.y >= part.max.y,
+            Compass::East => other.min.x == part.max.x && other.min.y <= part.min.y && other.max.y >= part.max.y,
+            Compass::South => other.max.y == part.min.y && other.min.x <= part.min.x && other.max.x >= part.max.x,
+            Compass::North => other.min.y == part.max.y && other.min.x <= part.min.x && other.max.x >= part.max.x,
+            Compass::None => false,
+        }
+    };
+    let inset = |side: Compass| -> f32 {
+        match room.parts().iter().find(|other| *other != part && covers(other, side)) {
+            Some(other) if covers_back(part, other, side) => 0.,
+            Some(_) => -radius,
+            None => radius,
+        }
+    };
+    let min = Vec2::new(part.min.x + inset(Compass::West), part.min.y + inset(Compass::South));
+    let max = Vec2::new(part.max.x - inset(Compass::East), part.max.y - inset(Compass::North));
+    if max.x > min.x && max.y > min.y {
+        Some(BoundingRect::new(min, max))
+    } else {
+        None
+    }
+}
+
+/// True if `part` in turn covers the whole of the side of `other` facing it
+fn covers_back(part: &BoundingRect<f32>, other: &BoundingRect<f32>, side: Compass) -> bool {
+    match side {
+        Compass::West | Compass::East => part.min.y <= other.min.y && part.max.y >= other.max.y,
+        _ => part.min.x <= other.min.x && part.max.x >= other.max.x,
+    }
+}
+
+/// The walkable part of a doorway with the given ends, reaching across the gap either side of the wall
+fn doorway(position: Vec2<f32>, span: (Vec2<f32>, Vec2<f32>), radius: f32) -> BoundingRect<f32> {
+    let (start, end) = span;
+    let horizontal = start.y == end.y;
+    let half = ((start.distance(end) / 2.) - radius).max(MIN_PASSAGE / 2.);
+    if horizontal {
+        BoundingRect::new(Vec2::new(position.x - half, position.y - radius), Vec2::new(position.x + half, position.y + radius))
+    } else {
+        BoundingRect::new(Vec2::new(position.x - radius, position.y - half), Vec2::new(position.x + radius, position.y + half))
+    }
+}
+
+/// Links two touching polygons along the part of their boundaries they share
+fn join(polys: &mut [NavPoly], a: usize, b: usize) {
+    let (ra, rb) = (polys[a].rect, polys[b].rect);
+    let min = Vec2::partial_max(ra.min, rb.min);
+    let max = Vec2::partial_min(ra.max, rb.max);
+    let edge = (min, max);
+    polys[a].links.push(NavLink { target: b, edge });
+    polys[b].links.push(NavLink { target: a, edge });
+}
+
+#[test]
+fn navmesh_obj_test() {
+    let rect = BoundingRect::new(Vec2::new(0., 0.), Vec2::new(2., 10.));
+    let mesh = NavMesh { radius: 0.5, polys: vec![NavPoly { rect, area: NavArea::Room(0), links: Vec::new() }] };
+    let count = |obj: &str, kind: &str| obj.lines().filter(|l| l.starts_with(kind)).count();
+
+    let obj = mesh.to_obj();
+    assert_eq!((count(&obj, "v "), count(&obj, "f ")), (4, 1));
+
+    // Cut into four strips along the module, each face using the vertices either side of it
+    let obj = mesh.to_obj_with(3., |p| Vec3::new(p.x as f64, 1., p.y as f64));
+    assert_eq!((count(&obj, "v "), count(&obj, "f ")), (10, 4));
+    assert!(obj.lines().filter(|l| l.starts_with("v ")).all(|l| l.split(' ').nth(2) == Some("1")));
+    assert_eq!(obj.lines().last(), Some("f 7 8 10 9"));
+}
+
+#[test]
+fn navmesh_find_poly_test() {
+    let rect = |x0: f32, x1: f32| BoundingRect::new(Vec2::new(x0, 0.), Vec2::new(x1, 2.));
+    let poly = |x0: f32, x1: f32, i: usize| NavPoly { rect: rect(x0, x1), area: NavArea::Room(i), links: Vec::new() };
+    let mut polys = vec![poly(0., 2., 0), poly(2., 4., 1)];
+    join(&mut polys, 0, 1);
+    let mesh = NavMesh { radius: 0.5, polys };
+
+    // A point on the edge two polygons share belongs to the first, and the second is a step away
+    assert_eq!(mesh.find_poly(Vec2::new(2., 1.)), Some(0));
+    assert_eq!(mesh.polys[0].links[0].edge, (Vec2::new(2., 0.), Vec2::new(2., 2.)));
+    assert_eq!(mesh.find_poly(Vec2::new(3., 1.)), Some(1));
+    assert_eq!(mesh.find_poly(Vec2::new(5., 1.)), None);
+    assert!(mesh.reachable(0).iter().all(|r| *r));
+}
+
+#[test]
+fn navmesh_compound_test() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32| BoundingRect::new(Vec2::new(x0, y0), Vec2::new(x1, y1));
+    let mut room = Room::new(rect(0., 0., 2., 6.));
+    room.absorb(&Room::new(rect(2., 0., 5., 2.)));
+    let radius = 0.5;
+    let mesh = NavMesh::new(&[room], &[], &[], &rect(0., 0., 5., 6.), radius);
+
+    // One floor per part, the shorter reaching round the corner to meet the taller along its inset wall
+    assert_eq!(mesh.polys.len(), 2);
+    assert_eq!(mesh.polys[0].rect, rect(0.5, 0.5, 1.5, 5.5));
+    assert_eq!(mesh.polys[1].rect, rect(1.5, 0.5, 4.5, 1.5));
+    assert!(mesh.polys.iter().all(|p| p.area == NavArea::Room(0) && p.links.len() == 1));
+    assert_eq!(mesh.polys[0].links[0].edge, (Vec2::new(1.5, 0.5), Vec2::new(1.5, 1.5)));
+}
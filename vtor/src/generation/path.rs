@@ -0,0 +1,168 @@
+use vek::{Vec2, Vec3};
+
+/// A position in a room, flat for module layouts or with height for volumes
+pub(crate) trait Point: Copy {
+    fn distance(self, other: Self) -> f32;
+}
+
+impl Point for Vec2<f32> {
+    fn distance(self, other: Self) -> f32 {
+        Vec2::distance(self, other)
+    }
+}
+
+impl Point for Vec3<f32> {
+    fn distance(self, other: Self) -> f32 {
+        Vec3::distance(self, other)
+    }
+}
+
+/// Somewhere a path can cross from one room into another: a door, or the end of a link with no door of its own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Crossing<P = Vec2<f32>> {
+    pub rooms: [usize; 2],
+    /// Where the crossing is, in the space of each room. These differ where the rooms lie in different modules, or on different decks.
+    pub positions: [P; 2],
+    /// Multiplies the distance walked to reach the crossing
+    pub weight: f32,
+    /// Distance covered passing through the crossing itself, such as the height climbed between decks. Also multiplied by the weight.
+    pub climb: f32,
+}
+
+/// A route through a room graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path<P = Vec2<f32>> {
+    /// Every room passed through, including both ends
+    pub rooms: Vec<usize>,
+    /// The start, each crossing in turn, and the end, along with the room whose space each point is given in
+    pub points: Vec<(usize, P)>,
+    pub cost: f32,
+}
+
+/// The cheapest route from a point in one room to a point in another, walking from crossing to crossing.
+/// `estimate` must never overestimate the remaining cost from a point in a room, or the route found may not be the cheapest.
+pub(crate) fn find<P, E>(crossings: &[Crossing<P>], rooms: usize, from: (usize, P), to: (usize, P), estimate: E) -> Option<Path<P>>
+where P: Point, E: Fn(usize, P) -> f32 {
+    let mut by_room = vec![Vec::new(); rooms];
+    for (i, crossing) in crossings.iter().enumerate() {
+        by_room[crossing.rooms[0]].push(i);
+        by_room[crossing.rooms[1]].push(i);
+    }
+
+    // A node is a crossing along with the side it was entered from, so 2i + side. The last two are the start and end.
+    let n = 2 * crossings.len() + 2;
+    let (start, end) = (n - 2, n - 1);
+    let place = |node: usize| -> (usize, P) {
+        if node == start {
+            from
+        } else if node == end {
+            to
+        } else {
+            // Standing on the far side of the crossing, in the room it leads into
+            let (crossing, side) = (&crossings[node / 2], node % 2);
+            (crossing.rooms[1 - side], crossing.positions[1 - side])
+        }
+    };
+
+    let mut cost = vec![f32::MAX; n];
+    let mut previous: Vec<Option<usize>> = vec![None; n];
+    let mut done = vec![false; n];
+    cost[start] = 0.;
+
+    // A*, with a linear scan for the next node as in corridor routing
+    loop {
+        let mut current = None;
+        let mut cheapest = f32::MAX;
+        for i in 0..n {
+            if !done[i] && cost[i] < f32::MAX {
+                let (room, position) = place(i);
+                let guess = cost[i] + estimate(room, position);
+                if guess < cheapest {
+                    cheapest = guess;
+                    current = Some(i);
+                }
+            }
+        }
+        let current = current?;
+        if current == end {
+            break;
+        }
+        done[current] = true;
+
+        let (room, position) = place(current);
+        if room == to.0 {
+            let step = position.distance(to.1);
+            if cost[current] + step < cost[end] {
+                cost[end] = cost[current] + step;
+                previous[end] = Some(current);
+            }
+        }
+        for i in by_room[room].iter().copied() {
+            let crossing = &crossings[i];
+            let side = if crossing.rooms[0] == room { 0 } else { 1 };
+            let next = 2 * i + side;
+            if done[next] {
+                continue;
+            }
+            let step = (position.distance(crossing.positions[side]) + crossing.climb) * crossing.weight;
+            if cost[current] + step < cost[next] {
+                cost[next] = cost[current] + step;
+                previous[next] = Some(current);
+            }
+        }
+    }
+
+    let mut chain = vec![end];
+    while let Some(p) = previous[*chain.last().unwrap()] {
+        chain.push(p);
+    }
+    chain.reverse();
+
+    let mut rooms = vec![from.0];
+    let mut points = vec![from];
+    for node in chain[1..chain.len() - 1].iter() {
+        let (crossing, side) = (&crossings[node / 2], node % 2);
+        points.push((crossing.rooms[side], crossing.positions[side]));
+        rooms.push(crossing.rooms[1 - side]);
+    }
+    points.push(to);
+
+    Some(Path {
+        rooms,
+        points,
+        cost: cost[end],
+    })
+}
+
+#[test]
+fn find_test() {
+    // A row of three rooms, 0 - 1 - 2, with a costly shortcut from 0 straight to 2
+    let crossing = |a: usize, b: usize, x: f32, weight: f32| Crossing {
+        rooms: [a, b],
+        positions: [Vec2::new(x, 0.); 2],
+        weight,
+        climb: 0.,
+    };
+    let mut crossings = vec![crossing(0, 1, 10., 1.), crossing(1, 2, 20., 1.), crossing(2, 0, 15., 5.)];
+    let from = (0, Vec2::new(5., 0.));
+    let to = (2, Vec2::new(25., 0.));
+
+    let path = find(&crossings, 3, from, to, |_, p| p.distance(to.1)).unwrap();
+    assert_eq!(path.rooms, vec![0, 1, 2]);
+    assert_eq!(path.points.len(), 4);
+    assert!((path.cost - 20.).abs() < 1e-4);
+
+    // Once the shortcut is cheap enough, it wins
+    crossings[2].weight = 0.5;
+    let path = find(&crossings, 3, from, to, |_, _| 0.).unwrap();
+    assert_eq!(path.rooms, vec![0, 2]);
+    assert_eq!(path.points[1], (0, Vec2::new(15., 0.)));
+
+    // Room 3 has no way in
+    assert!(find(&crossings, 4, from, (3, Vec2::zero()), |_, _| 0.).is_none());
+
+    // Climbing is priced at the crossing's weight, on top of the walk to reach it
+    let crossings = [Crossing { rooms: [0, 1], positions: [Vec3::new(1., 0., 0.), Vec3::new(1., 0., 3.)], weight: 2., climb: 3. }];
+    let path = find(&crossings, 2, (0, Vec3::zero()), (1, Vec3::new(1., 0., 3.)), |_, _| 0.).unwrap();
+    assert!((path.cost - 8.).abs() < 1e-4);
+}
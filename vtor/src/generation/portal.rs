@@ -1,30 +1,28 @@
 use prima::core::maths::*;
 use vek::{Rect, Vec2};
+use crate::geom::Compass;
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-pub enum NESW {
-    North,
-    East,
-    South,
-    West
-}
-
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Orientation {
     Vertical,
     Horizontal,
 }
 
 /// A portal is just a door attached to a room, refferenced in links
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Portal {
+    /// Center of the doorway
     pub position: Vec2<f32>,
+    /// Clear width of the doorway, not including its frame
+    pub width: f32,
+    /// The rooms either side of the door, lowest index first
+    pub rooms: (usize, usize),
     orientation: Orientation,
 }
 
 impl Portal {
-    pub fn new(position: Vec2<f32>, direction: NESW) -> Self {
-        let orientation = if direction == NESW::North || direction == NESW::South {
+    pub fn new(position: Vec2<f32>, direction: Compass, width: f32, rooms: (usize, usize)) -> Self {
+        let orientation = if direction == Compass::North || direction == Compass::South {
             Orientation::Horizontal
         } else {
             Orientation::Vertical
@@ -32,21 +30,36 @@ impl Portal {
 
         Self {
             position,
+            width,
+            rooms,
             orientation,
         }
     }
 
-    pub fn from_rect_edge(rect: &Rect<f32,f32>, direction: NESW, offset: f32) -> Self {
+    pub fn from_rect_edge(rect: &Rect<f32,f32>, direction: Compass, offset: f32, width: f32, rooms: (usize, usize)) -> Self {
         Self::new(match direction {
-            NESW::North => Vec2::new(lerp(rect.x, rect.x + rect.w, offset), rect.y + rect.h),
-            NESW::South => Vec2::new(lerp(rect.x, rect.x + rect.w, offset), rect.y),
-            NESW::East => Vec2::new(rect.x + rect.w, lerp(rect.y, rect.y + rect.h, offset)),
-            NESW::West => Vec2::new(rect.x, lerp(rect.y, rect.y + rect.h, offset)),
-        }, direction)
+            Compass::North => Vec2::new(lerp(rect.x, rect.x + rect.w, offset), rect.y + rect.h),
+            Compass::South => Vec2::new(lerp(rect.x, rect.x + rect.w, offset), rect.y),
+            Compass::East => Vec2::new(rect.x + rect.w, lerp(rect.y, rect.y + rect.h, offset)),
+            _ => Vec2::new(rect.x, lerp(rect.y, rect.y + rect.h, offset)),
+        }, direction, width, rooms)
+    }
+
+    /// The two ends of the doorway
+    pub fn span(&self) -> (Vec2<f32>, Vec2<f32>) {
+        let half = match self.orientation {
+            Orientation::Horizontal => Vec2::new(self.width / 2., 0.),
+            Orientation::Vertical => Vec2::new(0., self.width / 2.),
+        };
+        (self.position - half, self.position + half)
+    }
+
+    pub fn connects(&self, room: usize) -> bool {
+        self.rooms.0 == room || self.rooms.1 == room
     }
 
     /// Finds the position on the given rectangle edge that this sits
-    pub fn position_on_edge(&self, rect: &Rect<f32,f32>) -> Option<(NESW, f32)> {
+    pub fn position_on_edge(&self, rect: &Rect<f32,f32>) -> Option<(Compass, f32)> {
 
         let x = self.position.x;
         let y = self.position.y;
@@ -57,21 +70,21 @@ impl Portal {
         if self.orientation == Orientation::Vertical {
             if y_in_bounds && x == rect.x {
                 // West edge
-                return Some((NESW::West, inverse_lerp(rect.y, rect.y + rect.h, y)));
+                return Some((Compass::West, inverse_lerp(rect.y, rect.y + rect.h, y)));
             } else if y_in_bounds && x == rect.x + rect.w {
                 // East edge
-                return Some((NESW::East, inverse_lerp(rect.y, rect.y + rect.h, y)));
+                return Some((Compass::East, inverse_lerp(rect.y, rect.y + rect.h, y)));
             }
             return None;
         };
         
         if x_in_bounds && y == rect.y {
             // South edge
-            return Some((NESW::South, inverse_lerp(rect.x, rect.x + rect.w, x)));
+            return Some((Compass::South, inverse_lerp(rect.x, rect.x + rect.w, x)));
         } else if x_in_bounds && y == rect.y + rect.h {
             // North edge
-            return Some((NESW::North, inverse_lerp(rect.x, rect.x + rect.w, x)));
+            return Some((Compass::North, inverse_lerp(rect.x, rect.x + rect.w, x)));
         }
         None
     }
-}
\ No newline at end of file
+}
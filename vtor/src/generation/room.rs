@@ -1,12 +1,29 @@
-use vek::{Extent2, Rgb};
+use vek::{Extent2, Rgb, Vec2};
 use prima::geom::{BoundingRect, Line};
 use prima::render::*;
-use super::{Link, LinkType, IMG_SCALE};
+use super::{Link, LinkType, RoomStats, IMG_SCALE};
+
+/// What a room is used for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoomKind {
+    Room,
+    /// A stretch of corridor laid down to join two rooms that do not share a wall
+    Corridor,
+}
+
+/// Corners closer than this are the same corner
+const TOLERANCE: f32 = 1e-4;
 
 #[derive(Clone)]
 pub struct Room {
+    /// The floor of the room, or for compound rooms the rect bounding it, see `parts`
     pub rect: BoundingRect<f32>,
+    /// The rects making up the floor of a compound room, empty for a plain rectangle
+    parts: Vec<BoundingRect<f32>>,
     pub value: f32,
+    pub kind: RoomKind,
+    /// Filled in by `ModuleFactory::calculate_statistics`
+    pub stats: RoomStats,
     links: Vec<Link>,
 }
 
@@ -14,26 +31,109 @@ impl Room {
     pub fn new(rect: BoundingRect<f32>) -> Self {
         Self {
             rect,
+            parts: Vec::new(),
             links: Vec::new(),
-            value: 0.
+            value: 0.,
+            kind: RoomKind::Room,
+            stats: RoomStats::default(),
         }
     }
 
+    pub fn corridor(rect: BoundingRect<f32>) -> Self {
+        Self {
+            kind: RoomKind::Corridor,
+            ..Self::new(rect)
+        }
+    }
+
+    /// Size of the bounding rect, which for compound rooms is more than the floor itself
     pub fn size(&self) -> Extent2<f32> {
         self.rect.size()
     }
 
+    /// The non-overlapping rects making up the floor
+    pub fn parts(&self) -> &[BoundingRect<f32>] {
+        if self.parts.is_empty() {
+            std::slice::from_ref(&self.rect)
+        } else {
+            &self.parts
+        }
+    }
+
+    /// True if the floor is made up of more than one rect
+    pub fn is_compound(&self) -> bool {
+        !self.parts.is_empty()
+    }
+
+    /// Floor area
+    pub fn area(&self) -> f32 {
+        self.parts().iter().map(|p| p.size().w * p.size().h).sum()
+    }
+
+    /// A point on the floor near the middle of the room: the centre of its largest part
+    pub fn centre(&self) -> Vec2<f32> {
+        let area = |p: &&BoundingRect<f32>| p.size().w * p.size().h;
+        self.parts().iter().max_by(|a, b| area(a).partial_cmp(&area(b)).unwrap()).unwrap().center()
+    }
+
+    /// Takes on the floor of another room, which must share a wall with this one
+    pub fn absorb(&mut self, other: &Room) {
+        let mut parts = self.parts().to_vec();
+        parts.extend_from_slice(other.parts());
+        self.rect = BoundingRect::new(Vec2::partial_min(self.rect.min, other.rect.min), Vec2::partial_max(self.rect.max, other.rect.max));
+        self.parts = parts;
+    }
+
+    /// Corners of the floor, anticlockwise, with straight runs of wall merged into one
+    pub fn outline(&self) -> Vec<Vec2<f32>> {
+        let parts = self.parts();
+        let same = |a: Vec2<f32>, b: Vec2<f32>| a.distance(b) < TOLERANCE;
+
+        // Every part's walls, anticlockwise, cut wherever another part's corner lies along them
+        let all: Vec<Vec2<f32>> = parts.iter().flat_map(|p| corners(p).to_vec()).collect();
+        let mut walls = Vec::new();
+        for part in parts.iter() {
+            let c = corners(part);
+            for k in 0..4 {
+                let (start, end) = (c[k], c[(k + 1) % 4]);
+                let mut cuts: Vec<Vec2<f32>> = all.iter().copied().filter(|p| between(*p, start, end)).collect();
+                cuts.sort_by(|a, b| a.distance(start).partial_cmp(&b.distance(start)).unwrap());
+                let mut from = start;
+                for cut in cuts.into_iter().chain(std::iter::once(end)) {
+                    if !same(from, cut) {
+                        walls.push((from, cut));
+                        from = cut;
+                    }
+                }
+            }
+        }
+
+        // Walls between two parts run both ways, and so cancel out
+        let outer: Vec<(Vec2<f32>, Vec2<f32>)> = walls.iter().copied()
+            .filter(|(a, b)| !walls.iter().any(|(c, d)| same(*a, *d) && same(*b, *c)))
+            .collect();
+
+        let mut outline = vec![outer[0].0];
+        let mut at = outer[0].1;
+        while !same(at, outline[0]) && outline.len() <= outer.len() {
+            outline.push(at);
+            at = outer.iter().find(|(a, _)| same(*a, at)).unwrap().1;
+        }
+        // Drop corners in the middle of a straight run
+        let n = outline.len();
+        (0..n).filter(|i| {
+            let (prev, here, next) = (outline[(i + n - 1) % n], outline[*i], outline[(i + 1) % n]);
+            let (u, v) = (here - prev, next - here);
+            (u.x * v.y - u.y * v.x).abs() > TOLERANCE
+        }).map(|i| outline[i]).collect()
+    }
+
     pub fn connected(&self) -> Vec<usize> {
         self.links.iter().map(|x| x.target).collect()
     }
 
     pub fn link_type(&self, index: usize) -> Option<LinkType> {
-        if self.is_linked(index) {
-            let link = self.links[index];
-            Some(link.link_type)
-        } else {
-            None    
-        }
+        self.links.iter().find(|l| l.target == index).map(|l| l.link_type)
     }
 
     pub fn links(&self) -> Vec<&Link> {
@@ -48,16 +148,52 @@ impl Room {
         self.connected().contains(&other)
     }
 
+    pub fn unlink(&mut self, other: usize) {
+        self.links.retain(|l| l.target != other);
+    }
+
     pub fn link(&mut self, other: usize, link_type: LinkType) {
         let link = Link::new(other, link_type);
         if !self.links.contains(&link) {
             self.links.push(link);
         }
     }
+
+    /// Points direct links at their wall's new index, after some of the module's intersects were dropped
+    pub(crate) fn remap_intersects(&mut self, remap: &[Option<usize>]) {
+        for link in self.links.iter_mut() {
+            if let LinkType::Direct(r) = &mut link.link_type {
+                r.index = remap[r.index].expect("a linked wall is never dropped");
+            }
+        }
+    }
+}
+
+/// Corners of a rect, anticlockwise from the south west
+fn corners(rect: &BoundingRect<f32>) -> [Vec2<f32>; 4] {
+    [rect.min, Vec2::new(rect.max.x, rect.min.y), rect.max, Vec2::new(rect.min.x, rect.max.y)]
+}
+
+/// True if `p` lies on the wall from `start` to `end`, short of either end
+fn between(p: Vec2<f32>, start: Vec2<f32>, end: Vec2<f32>) -> bool {
+    let (u, v) = (end - start, p - start);
+    let along = u.dot(v) / u.magnitude_squared();
+    (u.x * v.y - u.y * v.x).abs() < TOLERANCE && along > 0. && along < 1. && p.distance(start) > TOLERANCE && p.distance(end) > TOLERANCE
 }
 
 impl Draw<f32> for Room {
     fn draw(&self, image: &mut RgbImage, colour: Rgb<u8>) {
+        if self.is_compound() {
+            let outline = self.outline();
+            for (i, start) in outline.iter().enumerate() {
+                Line {
+                    start: *start * IMG_SCALE as f32,
+                    end: outline[(i + 1) % outline.len()] * IMG_SCALE as f32,
+                }.draw(image, colour);
+            }
+            return;
+        }
+
         let boundingbox = BoundingRect {
                 min: self.rect.min * IMG_SCALE as f32,
                 max: self.rect.max * IMG_SCALE as f32,
@@ -72,4 +208,27 @@ impl Draw<f32> for Room {
         //     }.draw(image, Rgb::new(0,0,255));
         // }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn compound_room_test() {
+    // An L: a tall part with a shorter one against the bottom of its east wall
+    let mut room = Room::new(BoundingRect::new(Vec2::new(0., 0.), Vec2::new(2., 6.)));
+    room.absorb(&Room::new(BoundingRect::new(Vec2::new(2., 0.), Vec2::new(5., 2.))));
+    assert!(room.is_compound());
+    assert_eq!(room.parts().len(), 2);
+    assert_eq!(room.rect, BoundingRect::new(Vec2::zero(), Vec2::new(5., 6.)));
+    assert!((room.area() - 18.).abs() < 1e-4);
+    assert_eq!(room.centre(), Vec2::new(1., 3.));
+
+    let outline = room.outline();
+    let expected = [(0., 0.), (5., 0.), (5., 2.), (2., 2.), (2., 6.), (0., 6.)];
+    assert_eq!(outline.len(), expected.len());
+    let start = outline.iter().position(|p| *p == Vec2::zero()).unwrap();
+    for (k, (x, y)) in expected.iter().enumerate() {
+        assert_eq!(outline[(start + k) % outline.len()], Vec2::new(*x, *y));
+    }
+
+    // A U, with the base cut where each arm meets it
+    room.absorb(&Room::new(BoundingRect::new(Vec2::new(5., 0.), Vec2::new(7., 6.))));
+    assert_eq!(room.outline().len(), 8);
+}
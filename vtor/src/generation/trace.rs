@@ -0,0 +1,185 @@
+use prima::geom::BoundingRect;
+use super::intersect::IntersectRef;
+use super::corridor;
+use super::mirror;
+use super::{Room, RoomKind, LinkType};
+
+/// The rule that chose which room to split, see `ModuleCfg::divide_area_chance` and `divide_disparity_chance`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Pick {
+    /// The room with the largest area
+    Area,
+    /// The room with the greatest difference between width and depth
+    Disparity,
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Axis {
+    /// Split across the module, at a given x
+    X,
+    /// Split along the module, at a given y
+    Y,
+}
+
+/// Why two rooms stopped being linked
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnlinkReason {
+    /// The shared wall is too short to fit a door
+    Narrow,
+    /// The door was dropped to keep the layout closer to a tree
+    Pruned,
+    /// The link was replaced by a corridor
+    Routed,
+}
+
+/// A single decision made while generating a module. Rooms are referred to by their index at the time of the step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    /// A room was added whole, at the end of the room list
+    Place { rect: BoundingRect<f32>, kind: RoomKind },
+    /// A room was split in two. The halves replace it at the end of the room list, in the order given.
+    Split { room: usize, pick: Pick, axis: Axis, at: f32, halves: [BoundingRect<f32>; 2] },
+    /// A split point was thrown away for breaking the size limits in the config
+    Reject { room: usize, axis: Axis, at: f32 },
+    /// No split of the room fit the size limits, so it is never picked again
+    Exhausted { room: usize },
+    /// A room was removed, leaving its space free
+    Remove { room: usize },
+    /// Space left free without ever being a room
+    Free { rect: BoundingRect<f32> },
+    /// A corridor was cut through a room, leaving it the given rect, or it was made a corridor whole
+    Reshape { room: usize, rect: BoundingRect<f32>, kind: RoomKind },
+    /// Room `b` was merged into room `a` to make a compound room, then removed
+    Merge { a: usize, b: usize },
+    /// Everything so far was reflected across a centre line, see `mirror::reflect`. Rooms come first and corridors after.
+    Mirror { axis: Axis, at: f32 },
+    /// Two rooms were linked. Direct links give the intersect as seen from `a`.
+    Link { a: usize, b: usize, link_type: LinkType },
+    Unlink { a: usize, b: usize, reason: UnlinkReason },
+}
+
+/// Every decision made while generating a module, in order
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Steps through the trace from an empty module, one decision at a time
+    pub fn replay(&self) -> Replay<'_> {
+        Replay {
+            steps: &self.steps,
+            next: 0,
+            state: Snapshot::default(),
+        }
+    }
+}
+
+/// The partial layout after some number of steps
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    pub rooms: Vec<Room>,
+    pub free: Vec<BoundingRect<f32>>,
+}
+
+impl Snapshot {
+    fn apply(&mut self, step: &Step) {
+        match *step {
+            Step::Place { rect, kind: RoomKind::Room } => self.rooms.push(Room::new(rect)),
+            Step::Place { rect, kind: RoomKind::Corridor } => {
+                // Corridors are carved out of free space
+                self.free = self.free.iter().flat_map(|f| corridor::subtract(f, &rect)).collect();
+                self.rooms.push(Room::corridor(rect));
+            }
+            Step::Split { room, halves, .. } => {
+                self.rooms.remove(room);
+                self.rooms.extend(halves.iter().map(|h| Room::new(*h)));
+            }
+            Step::Reject { .. } | Step::Exhausted { .. } => {}
+            Step::Remove { room } => self.free.push(self.rooms.remove(room).rect),
+            Step::Free { rect } => self.free.push(rect),
+            Step::Reshape { room, rect, kind } => {
+                self.rooms[room].rect = rect;
+                self.rooms[room].kind = kind;
+            }
+            Step::Merge { a, b } => {
+                let absorbed = self.rooms[b].clone();
+                self.rooms[a].absorb(&absorbed);
+                self.rooms.remove(b);
+            }
+            Step::Mirror { axis, at } => {
+                let rects = |kind: RoomKind| -> Vec<BoundingRect<f32>> {
+                    self.rooms.iter().filter(|r| r.kind == kind).map(|r| r.rect).collect()
+                };
+                let rooms = mirror::reflect(&rects(RoomKind::Room), axis, at).into_iter().map(Room::new);
+                let corridors = mirror::reflect(&rects(RoomKind::Corridor), axis, at).into_iter().map(Room::corridor);
+                self.rooms = rooms.chain(corridors).collect();
+                self.free = mirror::reflect(&self.free, axis, at);
+            }
+            Step::Link { a, b, link_type } => {
+                let back = match link_type {
+                    LinkType::Direct(r) => LinkType::Direct(IntersectRef { index: r.index, edge: r.edge.opposite() }),
+                    other => other,
+                };
+                self.rooms[a].link(b, link_type);
+                self.rooms[b].link(a, back);
+            }
+            Step::Unlink { a, b, .. } => {
+                self.rooms[a].unlink(b);
+                self.rooms[b].unlink(a);
+            }
+        }
+    }
+}
+
+/// Replays a trace, yielding the partial layout after each step
+pub struct Replay<'a> {
+    steps: &'a [Step],
+    next: usize,
+    state: Snapshot,
+}
+
+impl<'a> Replay<'a> {
+    /// The step that will be applied next, if any
+    pub fn peek(&self) -> Option<&'a Step> {
+        self.steps.get(self.next)
+    }
+
+    /// The layout as of the last step applied
+    pub fn current(&self) -> &Snapshot {
+        &self.state
+    }
+
+    /// Applies the next step, returning it along with the layout it leaves behind
+    pub fn step(&mut self) -> Option<(&'a Step, &Snapshot)> {
+        let step = self.steps.get(self.next)?;
+        self.next += 1;
+        self.state.apply(step);
+        Some((step, &self.state))
+    }
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        self.step().map(|(_, snapshot)| snapshot.clone())
+    }
+}
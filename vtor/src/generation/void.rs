@@ -0,0 +1,79 @@
+use crate::config::HumanCfg;
+use crate::geom::Compass;
+use prima::geom::BoundingRect;
+use vek::{Rgb, Vec2};
+
+/// Long, thin voids of at least this ratio are used for cable runs
+const CABLE_RUN_RATIO: f32 = 3.;
+
+/// What a void region is used for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VoidKind {
+    /// Low space for crawling through to reach pipework and machinery
+    Crawlspace,
+    /// Large enough to hold water or air tanks
+    Tank,
+    /// Too narrow or too stretched for anything but cabling and ducts
+    CableRun,
+}
+
+impl VoidKind {
+    /// Picks a use for a void based on its shape
+    pub fn classify(rect: &BoundingRect<f32>, humans: &HumanCfg) -> Self {
+        let size = rect.size();
+        let (short, long) = (size.w.min(size.h), size.w.max(size.h));
+        if short < humans.width() || long / short >= CABLE_RUN_RATIO {
+            VoidKind::CableRun
+        } else if short >= 2. * humans.height() {
+            VoidKind::Tank
+        } else {
+            VoidKind::Crawlspace
+        }
+    }
+
+    pub fn colour(&self) -> Rgb<u8> {
+        match self {
+            VoidKind::Crawlspace => Rgb::new(96,72,48),
+            VoidKind::Tank => Rgb::new(40,80,120),
+            VoidKind::CableRun => Rgb::new(160,120,0),
+        }
+    }
+}
+
+/// Space inside a module that is not part of the public room graph
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Void {
+    pub rect: BoundingRect<f32>,
+    pub kind: VoidKind,
+}
+
+/// What a service hatch opens into from its void
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HatchTarget {
+    Room(usize),
+    /// Another void, which is itself reachable
+    Void(usize),
+}
+
+/// A small service opening into a void, for maintenance access only
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hatch {
+    /// Centre of the opening
+    pub position: Vec2<f32>,
+    /// Which edge of the void the hatch lies on
+    pub edge: Compass,
+    pub width: f32,
+    pub void: usize,
+    pub target: HatchTarget,
+}
+
+impl Hatch {
+    /// The two ends of the opening
+    pub fn span(&self) -> (Vec2<f32>, Vec2<f32>) {
+        let half = match self.edge {
+            Compass::North | Compass::South => Vec2::new(self.width / 2., 0.),
+            _ => Vec2::new(0., self.width / 2.),
+        };
+        (self.position - half, self.position + half)
+    }
+}
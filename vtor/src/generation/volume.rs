@@ -0,0 +1,599 @@
+use crate::config::{ModuleCfg, HumanCfg, PathCfg};
+use crate::seed::{Seed, Subsystem};
+use super::circulation::{Connector, ConnectorKind};
+use super::path::{self, Crossing, Path};
+use prima::geom::{BoundingBox, BoundingRect};
+use prima::core::maths::*;
+use vek::{Extent3, Vec2, Vec3};
+
+/// How many split points are tried before a room is left whole
+const MAX_SPLIT_ATTEMPTS: usize = 8;
+
+/// A face of a box-shaped room. Module space runs x across the module, y along it and z up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Face {
+    West,
+    East,
+    South,
+    North,
+    Down,
+    Up,
+}
+
+impl Face {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Face::West => Face::East,
+            Face::East => Face::West,
+            Face::South => Face::North,
+            Face::North => Face::South,
+            Face::Down => Face::Up,
+            Face::Up => Face::Down,
+        }
+    }
+
+    /// True for the floor and ceiling
+    pub fn is_vertical(&self) -> bool {
+        *self == Face::Down || *self == Face::Up
+    }
+}
+
+/// How two rooms sharing a face are joined
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Opening {
+    /// A doorway through a wall, between rooms with the same floor level
+    Door,
+    /// A hatch through a floor or ceiling
+    Hatch,
+    /// A ladder, stairs or lift between decks, see `VolumeFactory::place_connectors`
+    Connector(ConnectorKind),
+}
+
+/// One room's view of a face it shares with another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeLink {
+    pub target: usize,
+    /// The face of this room the other room lies against
+    pub face: Face,
+    /// How the rooms are joined, or None if the shared face is too small for a door or hatch
+    pub opening: Option<Opening>,
+    /// Centre of the opening, or of the shared face if there is none
+    pub position: Vec3<f32>,
+}
+
+/// A box-shaped room, which may span several storeys
+#[derive(Clone, Debug)]
+pub struct Volume {
+    pub bounds: BoundingBox<f32>,
+    links: Vec<VolumeLink>,
+}
+
+impl Volume {
+    pub fn new(bounds: BoundingBox<f32>) -> Self {
+        Self {
+            bounds,
+            links: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> Extent3<f32> {
+        let size = self.bounds.max - self.bounds.min;
+        Extent3::new(size.x, size.y, size.z)
+    }
+
+    /// Every room sharing a face with this one, whether or not there is a way through
+    pub fn links(&self) -> &[VolumeLink] {
+        &self.links
+    }
+
+    /// The rooms that can be reached directly from this one
+    pub fn connected(&self) -> Vec<usize> {
+        self.links.iter().filter(|l| l.opening.is_some()).map(|l| l.target).collect()
+    }
+
+    pub fn link(&self, other: usize) -> Option<&VolumeLink> {
+        self.links.iter().find(|l| l.target == other)
+    }
+}
+
+/// The 3D counterpart to `ModuleFactory`. Splits the module's bounding box along all three axes into box-shaped rooms.
+/// Vertical splits only fall on deck boundaries, so rooms are whole storeys tall, and rooms left spanning several
+/// storeys become double-height spaces.
+pub struct VolumeFactory {
+    pub bounds: BoundingBox<f32>,
+    pub rooms: Vec<Volume>,
+    /// Rooms removed to bring the count down to `room_count`
+    pub voids: Vec<BoundingBox<f32>>,
+    /// Height of a single deck, floor to floor
+    pub storey: f32,
+    /// Splits thrown away for breaking the size limits in the config
+    pub rejected: usize,
+    /// Ways between decks, each replacing the hatch between the rooms it joins
+    pub connectors: Vec<Connector>,
+    /// Decks with floors that no connector reaches from the bottom deck
+    pub unreached: Vec<usize>,
+    config: ModuleCfg,
+}
+
+impl VolumeFactory {
+    /// Splits the module into rooms. Decks are tall enough for a human to pass through a door with its frame above them.
+    pub fn new(config: ModuleCfg, humans: &HumanCfg) -> Self {
+        let bounds = BoundingBox::new(Vec3::zero(), Vec3::new(config.extent().w, config.extent().h, config.height));
+        let decks = (config.height / (humans.height() + config.door_frame)).floor().max(1.);
+        let storey = config.height / decks;
+
+        let mut rooms = vec![bounds];
+        let mut splittable = vec![true];
+        let mut rejected = 0;
+        let mut rng = Seed::new(config.seed).subsystem(Subsystem::Layout).rng();
+        let mut offset = clamp01(config.split_offset);
+        let degredation = clamp01(config.split_degredation);
+        let minimum = Vec3::new(config.min_room_width, config.min_room_depth, storey);
+
+        for _ in 0..config.divisions {
+            // Always split the largest room that still can be
+            let index = match (0..rooms.len()).filter(|i| splittable[*i]).max_by(|a, b| volume(&rooms[*a]).partial_cmp(&volume(&rooms[*b])).unwrap()) {
+                Some(index) => index,
+                None => break,
+            };
+            let room = rooms[index];
+            let size = room.max - room.min;
+
+            // Split across whichever axis has the most room to spare
+            let spare = size / minimum;
+            let axis = if spare.z >= spare.x && spare.z >= spare.y { 2 } else if spare.x >= spare.y { 0 } else { 1 };
+
+            let mut split = None;
+            for _ in 0..MAX_SPLIT_ATTEMPTS {
+                let r: f32 = rng.gen_range(offset, 1. - offset);
+                let mut at = room.min[axis] + r * size[axis];
+                if axis == 2 {
+                    // Measured from the module's floor, so every room's floor on a deck comes out exactly the same
+                    at = bounds.min.z + ((at - bounds.min.z) / storey).round() * storey;
+                }
+                let halves = split_box(&room, axis, at);
+                let fits = |b: &BoundingBox<f32>| {
+                    let s = b.max - b.min;
+                    s.z >= storey - 1e-3 && config.fits_room(s.x, s.y)
+                };
+                if fits(&halves[0]) && fits(&halves[1]) {
+                    split = Some(halves);
+                    break;
+                }
+                rejected += 1;
+            }
+            // Degrade offset so it becomes less centered
+            offset *= degredation;
+
+            match split {
+                Some(halves) => {
+                    rooms.remove(index);
+                    splittable.remove(index);
+                    rooms.extend(halves.iter());
+                    splittable.extend([true, true].iter());
+                }
+                None => splittable[index] = false,
+            }
+        }
+
+        let mut voids = Vec::new();
+        let splits = rooms.len() - 1;
+        for _ in 0..splits.saturating_sub(config.room_count) {
+            let index = rng.gen_range(0, rooms.len());
+            voids.push(rooms.remove(index));
+        }
+
+        Self {
+            bounds,
+            rooms: rooms.into_iter().map(Volume::new).collect(),
+            voids,
+            storey,
+            rejected,
+            connectors: Vec::new(),
+            unreached: Vec::new(),
+            config,
+        }
+    }
+
+    /// Generates the rooms, links every pair of rooms sharing a face, and joins the decks with connectors
+    pub fn default(config: ModuleCfg, humans: &HumanCfg) -> Self {
+        let mut module = Self::new(config, humans);
+        module.link_rooms(humans);
+        module.place_connectors(humans);
+        module
+    }
+
+    /// Records every shared face, on all six sides. Rooms on the same floor level get a door if the shared wall is long
+    /// enough for one, and rooms stacked on top of each other get a hatch if the shared floor is large enough.
+    pub fn link_rooms(&mut self, humans: &HumanCfg) {
+        let clearance = self.config.door_clearance(humans);
+        for i in 0..self.rooms.len() {
+            for j in i + 1..self.rooms.len() {
+                let (face, min, max) = match shared_face(&self.rooms[i].bounds, &self.rooms[j].bounds) {
+                    Some(shared) => shared,
+                    None => continue,
+                };
+                let span = max - min;
+                let centre = (min + max) / 2.;
+
+                let (opening, position) = if face.is_vertical() {
+                    let fits = span.x >= clearance && span.y >= clearance;
+                    (if fits { Some(Opening::Hatch) } else { None }, centre)
+                } else {
+                    // Doors stand on the floor, so both rooms need the same one
+                    let level = self.rooms[i].bounds.min.z == self.rooms[j].bounds.min.z;
+                    let across = if face == Face::West || face == Face::East { span.y } else { span.x };
+                    let fits = level && across >= clearance && span.z >= humans.height();
+                    (if fits { Some(Opening::Door) } else { None }, Vec3::new(centre.x, centre.y, min.z))
+                };
+
+                self.rooms[i].links.push(VolumeLink { target: j, face, opening, position });
+                self.rooms[j].links.push(VolumeLink { target: i, face: face.opposite(), opening, position });
+            }
+        }
+    }
+
+    /// Joins the decks so each can be reached from the bottom one. A lift is run up the tallest stack of rooms with room
+    /// for one, as long as it stops at three decks or more. Then each deck is joined to the one below by stairs, or a ladder
+    /// where stairs will not fit, favouring rooms already in reach and then the largest floors. Finally, more connectors
+    /// are added from rooms in reach into any deck still out of reach, for as long as one fits. Besides rooms stacked on top
+    /// of each other, stairs and ladders can climb the wall of a taller room to a door into a room with a higher floor.
+    pub fn place_connectors(&mut self, humans: &HumanCfg) {
+        if let Some((rooms, area)) = self.tallest_stack(humans) {
+            let rect = ConnectorKind::Lift.fit(&area, humans, self.storey).unwrap();
+            self.add_connector(Connector { kind: ConnectorKind::Lift, rect, rooms });
+        }
+
+        let decks = ((self.bounds.max.z - self.bounds.min.z) / self.storey).round() as usize;
+        for deck in 1..decks {
+            let z = self.bounds.min.z + deck as f32 * self.storey;
+            let reachable = self.reachable();
+            let mut candidates: Vec<Candidate> = self.candidates(humans).into_iter()
+                .filter(|c| (self.rooms[c.rooms[1]].bounds.min.z - z).abs() < 1e-3)
+                .collect();
+            candidates.sort_by(|a, b| {
+                let size = |r: &BoundingRect<f32>| r.size().w * r.size().h;
+                reachable[b.rooms[0]].cmp(&reachable[a.rooms[0]])
+                    .then(size(&b.area).partial_cmp(&size(&a.area)).unwrap())
+                    .then(a.rooms.cmp(&b.rooms))
+            });
+            if let Some(connector) = self.first_fit(&candidates, humans) {
+                self.add_connector(connector);
+            }
+        }
+
+        loop {
+            self.unreached = self.unreached_decks();
+            let reachable = self.reachable();
+            let candidates: Vec<Candidate> = self.candidates(humans).into_iter()
+                .filter(|c| {
+                    let [a, b] = c.rooms;
+                    let out_of_reach = if reachable[a] { b } else { a };
+                    reachable[a] != reachable[b] && self.unreached.contains(&self.deck(out_of_reach))
+                })
+                .collect();
+            match self.first_fit(&candidates, humans) {
+                Some(connector) => self.add_connector(connector),
+                None => break,
+            }
+        }
+    }
+
+    /// Every pair of rooms on different decks that a ladder or stairs could join, but no connector does yet
+    fn candidates(&self, humans: &HumanCfg) -> Vec<Candidate> {
+        let mut candidates = Vec::new();
+        for (i, room) in self.rooms.iter().enumerate() {
+            for link in room.links().iter().filter(|l| !matches!(l.opening, Some(Opening::Connector(_)))) {
+                let j = link.target;
+                let above = &self.rooms[j].bounds;
+                let rise = above.min.z - room.bounds.min.z;
+                if link.face == Face::Up {
+                    candidates.push(Candidate { rooms: [i, j], area: plan(&room.bounds, above), wall: None, rise });
+                } else if !link.face.is_vertical() && rise > 0. {
+                    // The door at the top needs headroom under whatever the rooms share of the wall
+                    let (_, min, max) = shared_face(&room.bounds, above).unwrap();
+                    if max.z - above.min.z >= humans.height() {
+                        let wall = BoundingRect::new(Vec2::new(min.x, min.y), Vec2::new(max.x, max.y));
+                        candidates.push(Candidate { rooms: [i, j], area: plan(&room.bounds, &room.bounds), wall: Some(wall), rise });
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Stairs for the first of the candidates they fit, or failing that, a ladder
+    fn first_fit(&self, candidates: &[Candidate], humans: &HumanCfg) -> Option<Connector> {
+        candidates.iter().find_map(|c| {
+            [ConnectorKind::Stairs, ConnectorKind::Ladder].iter().find_map(|kind| {
+                let rect = match c.wall {
+                    None => kind.fit(&c.area, humans, c.rise),
+                    Some(wall) => kind.fit_against(&c.area, &wall, humans, c.rise),
+                };
+                rect.map(|rect| Connector { kind: *kind, rect, rooms: c.rooms.to_vec() })
+            })
+        })
+    }
+
+    /// Decks holding the floor of at least one room, none of which can be reached from the bottom deck
+    fn unreached_decks(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        let mut floors: Vec<usize> = (0..self.rooms.len()).map(|i| self.deck(i)).collect();
+        floors.sort();
+        floors.dedup();
+        floors.into_iter().filter(|d| !(0..self.rooms.len()).any(|i| reachable[i] && self.deck(i) == *d)).collect()
+    }
+
+    /// The tallest stack of rooms, bottom up, whose shared floors have an area in common large enough for a lift
+    fn tallest_stack(&self, humans: &HumanCfg) -> Option<(Vec<usize>, BoundingRect<f32>)> {
+        let mut best: Option<(Vec<usize>, BoundingRect<f32>)> = None;
+        for start in 0..self.rooms.len() {
+            let bounds = self.rooms[start].bounds;
+            let mut open = vec![(vec![start], plan(&bounds, &bounds))];
+            while let Some((rooms, area)) = open.pop() {
+                let longest = best.as_ref().map(|b| b.0.len()).unwrap_or(2);
+                if rooms.len() > longest {
+                    best = Some((rooms.clone(), area));
+                }
+                let top = &self.rooms[*rooms.last().unwrap()];
+                for link in top.links().iter().filter(|l| l.face == Face::Up) {
+                    let above = &self.rooms[link.target].bounds;
+                    let shared = plan(&BoundingBox::new(area.min.with_z(0.), area.max.with_z(0.)), above);
+                    if ConnectorKind::Lift.fit(&shared, humans, self.storey).is_some() {
+                        let mut rooms = rooms.clone();
+                        rooms.push(link.target);
+                        open.push((rooms, shared));
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Opens each floor or wall the connector passes through, at the upper room's floor level
+    fn add_connector(&mut self, connector: Connector) {
+        let centre = connector.rect.center();
+        for pair in connector.rooms.windows(2) {
+            let (_, min, max) = shared_face(&self.rooms[pair[0]].bounds, &self.rooms[pair[1]].bounds).unwrap();
+            let position = Vec3::new(centre.x, centre.y, self.rooms[pair[1]].bounds.min.z);
+            let position = Vec3::partial_min(Vec3::partial_max(position, min), max);
+            for (room, other) in [(pair[0], pair[1]), (pair[1], pair[0])].iter() {
+                if let Some(link) = self.rooms[*room].links.iter_mut().find(|l| l.target == *other) {
+                    link.opening = Some(Opening::Connector(connector.kind));
+                    link.position = position;
+                }
+            }
+        }
+        self.connectors.push(connector);
+    }
+
+    /// Rooms that can be reached from the first room on the bottom deck
+    fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.rooms.len()];
+        let mut stack: Vec<usize> = (0..self.rooms.len()).find(|i| self.deck(*i) == 0).into_iter().collect();
+        for i in stack.iter() {
+            reached[*i] = true;
+        }
+        while let Some(i) = stack.pop() {
+            for j in self.rooms[i].connected() {
+                if !reached[j] {
+                    reached[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The cheapest walk between the middles of two rooms' floors. Climbing between decks is priced by `PathCfg`
+    /// for each kind of connector, with hatches climbed like a ladder.
+    pub fn find_path(&self, from: usize, to: usize, costs: &PathCfg) -> Option<Path<Vec3<f32>>> {
+        let mut crossings = Vec::new();
+        for (a, room) in self.rooms.iter().enumerate() {
+            for link in room.links().iter().filter(|l| l.target > a) {
+                let weight = match link.opening {
+                    None => continue,
+                    Some(Opening::Door) => costs.door(),
+                    Some(Opening::Hatch) => costs.connector(ConnectorKind::Ladder),
+                    Some(Opening::Connector(kind)) => costs.connector(kind),
+                };
+                // Each side of the crossing stands on its own room's floor
+                let floors = [room.bounds.min.z, self.rooms[link.target].bounds.min.z];
+                crossings.push(Crossing {
+                    rooms: [a, link.target],
+                    positions: [link.position.with_z(floors[0]), link.position.with_z(floors[1])],
+                    weight,
+                    climb: (floors[1] - floors[0]).abs(),
+                });
+            }
+        }
+        let floor = |i: usize| {
+            let b = self.rooms[i].bounds;
+            Vec3::new((b.min.x + b.max.x) / 2., (b.min.y + b.max.y) / 2., b.min.z)
+        };
+        path::find(&crossings, self.rooms.len(), (from, floor(from)), (to, floor(to)), |_, _| 0.)
+    }
+
+    /// Rooms spanning more than one deck
+    pub fn double_height(&self) -> Vec<usize> {
+        (0..self.rooms.len()).filter(|i| self.rooms[*i].size().d > self.storey * 1.5).collect()
+    }
+
+    /// The deck a room's floor is on, counting up from 0
+    pub fn deck(&self, room: usize) -> usize {
+        ((self.rooms[room].bounds.min.z - self.bounds.min.z) / self.storey).round() as usize
+    }
+}
+
+fn volume(b: &BoundingBox<f32>) -> f32 {
+    let s = b.max - b.min;
+    s.x * s.y * s.z
+}
+
+/// Two rooms on different decks that a ladder or stairs could join, standing in the lower room
+struct Candidate {
+    /// The lower room, then the upper
+    rooms: [usize; 2],
+    /// Where the connector can stand: the floor the rooms share if one is on top of the other, or else the lower room's floor
+    area: BoundingRect<f32>,
+    /// The stretch of wall between the rooms, if they stand side by side
+    wall: Option<BoundingRect<f32>>,
+    /// Height from the lower room's floor to the upper room's
+    rise: f32,
+}
+
+/// The area two boxes have in common, seen from above
+fn plan(a: &BoundingBox<f32>, b: &BoundingBox<f32>) -> BoundingRect<f32> {
+    let min = Vec3::partial_max(a.min, b.min);
+    let max = Vec3::partial_min(a.max, b.max);
+    BoundingRect::new(Vec2::new(min.x, min.y), Vec2::new(max.x, max.y))
+}
+
+fn split_box(b: &BoundingBox<f32>, axis: usize, at: f32) -> [BoundingBox<f32>; 2] {
+    let mut low = *b;
+    let mut high = *b;
+    low.max[axis] = at;
+    high.min[axis] = at;
+    [low, high]
+}
+
+/// Finds the face two boxes share, as the face of `a` along with the corners of the shared area.
+/// Returns None unless the boxes touch over an area, rather than just along an edge or at a corner.
+pub(crate) fn shared_face(a: &BoundingBox<f32>, b: &BoundingBox<f32>) -> Option<(Face, Vec3<f32>, Vec3<f32>)> {
+    let faces = [
+        (0, a.min.x == b.max.x, Face::West),
+        (0, a.max.x == b.min.x, Face::East),
+        (1, a.min.y == b.max.y, Face::South),
+        (1, a.max.y == b.min.y, Face::North),
+        (2, a.min.z == b.max.z, Face::Down),
+        (2, a.max.z == b.min.z, Face::Up),
+    ];
+    let (axis, _, face) = *faces.iter().find(|f| f.1)?;
+
+    let min = Vec3::partial_max(a.min, b.min);
+    let max = Vec3::partial_min(a.max, b.max);
+    let (u, v) = match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+    let extent = Vec2::new(max[u] - min[u], max[v] - min[v]);
+    if extent.x <= 0. || extent.y <= 0. {
+        return None;
+    }
+    Some((face, min, max))
+}
+
+#[test]
+fn shared_face_test() {
+    let a = BoundingBox::new(Vec3::new(0., 0., 0.), Vec3::new(4., 4., 3.));
+    let above = BoundingBox::new(Vec3::new(2., 1., 3.), Vec3::new(8., 8., 6.));
+    let beside = BoundingBox::new(Vec3::new(4., 2., 0.), Vec3::new(8., 8., 6.));
+    let edge = BoundingBox::new(Vec3::new(4., 4., 3.), Vec3::new(8., 8., 6.));
+
+    let (face, min, max) = shared_face(&a, &above).unwrap();
+    assert_eq!(face, Face::Up);
+    assert_eq!((min, max), (Vec3::new(2., 1., 3.), Vec3::new(4., 4., 3.)));
+    assert_eq!(shared_face(&above, &a).unwrap().0, Face::Down);
+    assert_eq!(shared_face(&a, &beside).unwrap().0, Face::East);
+    assert!(shared_face(&a, &edge).is_none());
+}
+
+#[test]
+fn volume_factory_test() {
+    let humans = HumanCfg::default();
+    let config = ModuleCfg {
+        seed: 73563,
+        room_count: 30,
+        divisions: 40,
+        extent: [32., 48.],
+        height: 12.,
+        ..ModuleCfg::default()
+    };
+    let module = VolumeFactory::default(config, &humans);
+
+    // Rooms and voids fill the module exactly
+    let total: f32 = module.rooms.iter().map(|r| volume(&r.bounds)).chain(module.voids.iter().map(volume)).sum();
+    assert!((total - volume(&module.bounds)).abs() < 1e-1);
+
+    let mut hatches = 0;
+    for (i, room) in module.rooms.iter().enumerate() {
+        // Every room is a whole number of storeys tall
+        let storeys = room.size().d / module.storey;
+        assert!((storeys - storeys.round()).abs() < 1e-3);
+        for link in room.links() {
+            let back = module.rooms[link.target].link(i).unwrap();
+            assert_eq!(back.face, link.face.opposite());
+            assert_eq!(back.opening, link.opening);
+            if link.opening == Some(Opening::Hatch) {
+                hatches += 1;
+                assert!(link.face.is_vertical());
+            }
+            if link.opening == Some(Opening::Door) {
+                assert_eq!(module.deck(i), module.deck(link.target));
+            }
+        }
+    }
+    assert!(hatches > 0);
+    assert!(!module.double_height().is_empty());
+    assert!(module.rooms.iter().any(|r| r.links().iter().any(|l| l.opening == Some(Opening::Door))));
+}
+
+#[test]
+fn connectors_test() {
+    let humans = HumanCfg::default();
+    let config = ModuleCfg {
+        seed: 73563,
+        room_count: 30,
+        divisions: 40,
+        extent: [32., 48.],
+        height: 12.,
+        ..ModuleCfg::default()
+    };
+    let module = VolumeFactory::default(config, &humans);
+    assert!(module.unreached.is_empty());
+    for connector in module.connectors.iter() {
+        for pair in connector.rooms.windows(2) {
+            let link = module.rooms[pair[0]].link(pair[1]).unwrap();
+            assert_eq!(link.opening, Some(Opening::Connector(connector.kind)));
+            assert!(module.rooms[pair[1]].bounds.min.z > module.rooms[pair[0]].bounds.min.z);
+        }
+    }
+    // Some rooms can only be reached by climbing the wall of a taller room
+    assert!(module.connectors.iter().any(|c| module.rooms[c.rooms[0]].link(c.rooms[1]).unwrap().face != Face::Up));
+
+    // Two stacks of three rooms side by side, joined by doors on every deck
+    let storey = 3.;
+    let boxes = (0..3).flat_map(|deck| {
+        let z = deck as f32 * storey;
+        vec![
+            BoundingBox::new(Vec3::new(0., 0., z), Vec3::new(10., 20., z + storey)),
+            BoundingBox::new(Vec3::new(10., 0., z), Vec3::new(20., 20., z + storey)),
+        ]
+    });
+    let mut module = VolumeFactory {
+        bounds: BoundingBox::new(Vec3::zero(), Vec3::new(20., 20., 3. * storey)),
+        rooms: boxes.map(Volume::new).collect(),
+        voids: Vec::new(),
+        storey,
+        rejected: 0,
+        connectors: Vec::new(),
+        unreached: Vec::new(),
+        config: ModuleCfg::default(),
+    };
+    module.link_rooms(&humans);
+    module.place_connectors(&humans);
+
+    // The lift takes one stack, and stairs climb the other
+    let kinds: Vec<ConnectorKind> = module.connectors.iter().map(|c| c.kind).collect();
+    assert_eq!(kinds, vec![ConnectorKind::Lift, ConnectorKind::Stairs, ConnectorKind::Stairs]);
+    assert_eq!(module.connectors[0].rooms, vec![0, 2, 4]);
+
+    // Pathfinding prices the stairs against the lift
+    let costs: PathCfg = toml::from_str("lift = 50.0").unwrap();
+    let stairs = module.find_path(0, 4, &costs).unwrap();
+    assert_eq!(stairs.rooms, vec![0, 1, 3, 5, 4]);
+    let lift = module.find_path(0, 4, &PathCfg::default()).unwrap();
+    assert_eq!(lift.rooms, vec![0, 2, 4]);
+    assert!(lift.cost < stairs.cost);
+}
@@ -5,4 +5,16 @@ pub enum Compass {
     East,
     West,
     None,
-}
\ No newline at end of file
+}
+
+impl Compass {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Compass::North => Compass::South,
+            Compass::South => Compass::North,
+            Compass::East => Compass::West,
+            Compass::West => Compass::East,
+            Compass::None => Compass::None,
+        }
+    }
+}
@@ -16,10 +16,10 @@ impl<T> TBounds<T> where T: GeoNum {
     }
 
     pub fn min(&self) -> TPos<T> {
-        self.min.clone()
+        self.min
     }
     pub fn max(&self) -> TPos<T> {
-        self.max.clone()
+        self.max
     }
 
     pub fn contains(&self, other: &TBounds<T>) -> bool {
@@ -1,4 +1,6 @@
 pub mod geom;
 pub mod structures;
 pub mod config;
-pub mod generation;
\ No newline at end of file
+pub mod generation;
+pub mod seed;
+pub mod rng;
\ No newline at end of file
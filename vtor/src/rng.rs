@@ -0,0 +1,143 @@
+//! The random number generator used by every generator. Stations are shared between players by seed alone, so the
+//! stream must never change: this is PCG32 (PCG-XSH-RR with 64 bits of state, see https://www.pcg-random.org), with
+//! float and range sampling defined here rather than borrowed from a library that is free to change them.
+//! Any change to the output of this file changes every station, and must fail the known-answer tests below.
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    /// Seeds the generator as in the reference implementation. Generators with the same seed on different streams
+    /// give unrelated output.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    /// Seeds the generator on the default stream
+    pub fn from_seed(seed: u64) -> Self {
+        Self::new(seed, 0)
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Two draws, high bits first
+    pub fn next_u64(&mut self) -> u64 {
+        let high = self.next_u32() as u64;
+        (high << 32) | self.next_u32() as u64
+    }
+
+    /// Uniform in [0, 1), from the top 24 bits of one draw
+    pub fn gen_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform in [0, 1), from the top 53 bits of two draws
+    pub fn gen_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with the given probability
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        self.gen_f64() < p
+    }
+
+    /// Uniform in [low, high). Panics if the range is empty.
+    pub fn gen_range<T>(&mut self, low: T, high: T) -> T where T: SampleRange {
+        T::sample(self, low, high)
+    }
+
+    /// Uniform in [0, bound), without the bias of a plain modulo
+    fn bounded(&mut self, bound: u64) -> u64 {
+        // Draws below the threshold would make the low values slightly more likely
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let x = self.next_u64();
+            if x >= threshold {
+                return x % bound;
+            }
+        }
+    }
+
+    /// Fisher-Yates, walking down from the end of the slice
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.gen_range(0, i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Types that can be drawn uniformly from a half open range
+pub trait SampleRange: Copy {
+    fn sample(rng: &mut Pcg32, low: Self, high: Self) -> Self;
+}
+
+impl SampleRange for f32 {
+    fn sample(rng: &mut Pcg32, low: Self, high: Self) -> Self {
+        assert!(low < high, "empty range {}..{}", low, high);
+        // Rounding can land exactly on the upper bound, which is outside the range
+        let x = low + (high - low) * rng.gen_f32();
+        if x < high { x } else { low }
+    }
+}
+
+impl SampleRange for f64 {
+    fn sample(rng: &mut Pcg32, low: Self, high: Self) -> Self {
+        assert!(low < high, "empty range {}..{}", low, high);
+        let x = low + (high - low) * rng.gen_f64();
+        if x < high { x } else { low }
+    }
+}
+
+impl SampleRange for usize {
+    fn sample(rng: &mut Pcg32, low: Self, high: Self) -> Self {
+        assert!(low < high, "empty range {}..{}", low, high);
+        low + rng.bounded((high - low) as u64) as usize
+    }
+}
+
+#[test]
+fn pcg32_known_answer_test() {
+    // The first outputs of the reference implementation's demo, seeded with 42 on stream 54
+    let mut rng = Pcg32::new(42, 54);
+    let expected = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e];
+    for x in expected.iter() {
+        assert_eq!(rng.next_u32(), *x);
+    }
+}
+
+#[test]
+fn sampling_known_answer_test() {
+    // Locks the sampling built on top of the raw stream, so it cannot drift either
+    let mut rng = Pcg32::from_seed(73563);
+    assert_eq!(rng.next_u64(), 0xbabe_f8ca_d94d_f2ed);
+    assert_eq!(rng.gen_f32(), 0.902_908_8);
+    assert_eq!(rng.gen_f64(), 0.213_154_330_444_581_54);
+    assert_eq!(rng.gen_range(-2f32, 3.), -1.240_487_2);
+    let draws: Vec<usize> = (0..5).map(|_| rng.gen_range(0, 10)).collect();
+    assert_eq!(draws, vec![8, 4, 7, 9, 2]);
+    let mut items = [0, 1, 2, 3, 4, 5];
+    rng.shuffle(&mut items);
+    assert_eq!(items, [3, 1, 2, 0, 4, 5]);
+}
@@ -0,0 +1,92 @@
+//! Every random draw is made from a seed derived down a fixed hierarchy: station → sector → module → subsystem.
+//! Each step mixes in only its own label or index, so changing one module, or adding draws to one subsystem,
+//! leaves the streams of every other module and subsystem untouched.
+use crate::rng::Pcg32;
+
+/// The parts of a module's generation that draw random numbers, each from its own stream
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Subsystem {
+    /// Ranged and distribution-valued config fields
+    Config,
+    /// Dividing the module into rooms
+    Layout,
+    /// Door placement and which doors are kept
+    Links,
+    /// Furnishing and dressing rooms
+    Props,
+}
+
+impl Subsystem {
+    fn label(&self) -> &'static str {
+        match self {
+            Subsystem::Config => "config",
+            Subsystem::Layout => "layout",
+            Subsystem::Links => "links",
+            Subsystem::Props => "props",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Seed(u64);
+
+impl Seed {
+    pub fn new(value: u64) -> Self {
+        Seed(value)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    pub fn sector(&self, sector: usize) -> Self {
+        self.child("sector").index(sector as u64)
+    }
+
+    pub fn module(&self, module: usize) -> Self {
+        self.child("module").index(module as u64)
+    }
+
+    pub fn subsystem(&self, subsystem: Subsystem) -> Self {
+        self.child(subsystem.label())
+    }
+
+    /// A named stream below this one, e.g. a single config field or one pass of a subsystem
+    pub fn child(&self, label: &str) -> Self {
+        let mut hash = splitmix(self.0 ^ 0x6c62_272e_07bb_0142);
+        for byte in label.bytes() {
+            hash = splitmix(hash ^ byte as u64);
+        }
+        Seed(hash)
+    }
+
+    /// A numbered stream below this one
+    pub fn index(&self, index: u64) -> Self {
+        Seed(splitmix(splitmix(self.0) ^ index))
+    }
+
+    pub fn rng(&self) -> Pcg32 {
+        Pcg32::from_seed(self.0)
+    }
+}
+
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn seed_test() {
+    let station = Seed::new(73563);
+    let module = station.sector(1).module(2);
+    assert_eq!(module, Seed::new(73563).sector(1).module(2));
+
+    // Neighbouring addresses, and the same index at different levels, all get their own streams
+    assert_ne!(module, station.sector(2).module(1));
+    assert_ne!(station.sector(1), station.module(1));
+    assert_ne!(module.subsystem(Subsystem::Layout), module.subsystem(Subsystem::Links));
+    assert_ne!(module.child("room_count"), module.child("divisions"));
+    assert_ne!(Seed::new(0).index(0), Seed::new(0));
+}
@@ -2,6 +2,6 @@ mod station;
 mod module;
 mod sector;
 
-pub use station::Station;
+pub use station::{Station, StationPath};
 pub use module::Module;
 pub use sector::Sector;
\ No newline at end of file
@@ -1,20 +1,55 @@
 use prima::core::GeoNum;
 use prima::geom::BoundingBox;
 use crate::geom::{Arc};
+use crate::config::{ModuleCfg, Resolved};
+use crate::generation::ModuleAddress;
 
 #[derive(Clone)]
 pub struct Module<T> where T: GeoNum {
     uid: usize,
+    address: ModuleAddress,
     arc: Arc<T>,
     bbox: BoundingBox<T>,
+    cfg: Resolved<ModuleCfg>,
 }
 
 impl<T> Module<T> where T: GeoNum {
-    pub fn new(uid: usize, arc: Arc<T>, bbox: BoundingBox<T>) -> Self {
+    pub fn new(uid: usize, address: ModuleAddress, arc: Arc<T>, bbox: BoundingBox<T>, cfg: Resolved<ModuleCfg>) -> Self {
         Self {
             uid,
+            address,
             arc,
             bbox,
+            cfg,
         }
     }
+
+    /// Position around the ring, unique within the station
+    pub fn uid(&self) -> usize {
+        self.uid
+    }
+
+    pub fn address(&self) -> ModuleAddress {
+        self.address
+    }
+
+    /// The effective config for this module, with any sector and module overrides applied
+    pub fn cfg(&self) -> &ModuleCfg {
+        &self.cfg.value
+    }
+
+    /// The slice of the ring the module fills
+    pub fn arc(&self) -> &Arc<T> {
+        &self.arc
+    }
+
+    /// The box bounding the module's slice of the ring
+    pub fn bbox(&self) -> &BoundingBox<T> {
+        &self.bbox
+    }
+
+    /// The config along with where each value came from, and the distribution of any drawn values
+    pub fn resolved_cfg(&self) -> &Resolved<ModuleCfg> {
+        &self.cfg
+    }
 }
\ No newline at end of file
@@ -1,20 +1,34 @@
 use prima::core::GeoNum;
 use crate::geom::{Torus};
 use crate::config::*;
+use crate::generation::{ModuleAddress, BoundaryPort, ModuleFactory, NavMesh, Path};
+use crate::generation::path::{self, Crossing};
+use crate::geom::Compass;
 use super::Sector;
 use super::Module;
-use vek::Vec3;
+use vek::{Vec2, Vec3};
 use std::f64;
 
+/// A route that may run through several modules
+#[derive(Clone, Debug)]
+pub struct StationPath<T> where T: GeoNum {
+    /// Every room passed through, as (module, room), including both ends
+    pub rooms: Vec<(usize, usize)>,
+    /// The start, each door in turn, and the end, in world space
+    pub world: Vec<Vec3<T>>,
+    pub cost: f32,
+}
+
 pub struct Station<T> where T: GeoNum {
     pub(crate) torus: Torus<T>,
-    pub(crate) _config: Config,
+    pub(crate) config: Config,
     pub(crate) modules: Vec<Module<T>>,
 }
 
 
-impl<'a, T> Station<T> where T: GeoNum {
-    pub fn new(config: Config) -> Self {
+impl<T> Station<T> where T: GeoNum {
+    /// Lays out every module of the station, failing if a module's overrides cannot be resolved
+    pub fn new(config: Config) -> Result<Self, ConfigError> {
         let major: T = config.station.major().unwrap();
         let minor: T = config.station.minor().unwrap();
         let module_count = config.sectors.modules();
@@ -28,16 +42,18 @@ impl<'a, T> Station<T> where T: GeoNum {
             let sector = Sector::<T>::new(i, ang_incr);
             for (j, arc) in sector.arc().subdivide(module_count).iter().enumerate() {
                 let bbox = torus.make_arc_bbox(arc);
-                let module = Module::new(j * sector.uid(), *arc, bbox);
+                let cfg = config.resolve_module(i, j)?;
+                let address = ModuleAddress::new(sector.uid(), j);
+                let module = Module::new(address.ring_index(module_count), address, *arc, bbox, cfg);
                 modules.push(module);
             }
         }
 
-        Self {
+        Ok(Self {
             torus,
             modules,
-            _config: config,
-        }
+            config,
+        })
     }
 
     pub fn torus(&self) -> &Torus<T> {
@@ -47,10 +63,201 @@ impl<'a, T> Station<T> where T: GeoNum {
     pub fn module(&self, i: usize) -> &Module<T> {
         &self.modules[i]
     }
+
+    pub fn module_count(&self) -> usize {
+        self.modules.len()
+    }
+
+    /// The doors through the antispinward and spinward ends of the given module, in that order
+    pub fn ports(&self, i: usize) -> [BoundaryPort; 2] {
+        let n = self.modules.len();
+        let previous = (i + n - 1) % n;
+        let next = (i + 1) % n;
+        [
+            self.boundary_port(previous, i, Compass::South),
+            self.boundary_port(i, next, Compass::North),
+        ]
+    }
+
+    /// Generates the rooms of the given module, with its ports linked into the room graph
+    pub fn generate_module(&self, i: usize) -> ModuleFactory {
+        ModuleFactory::with_ports(self.modules[i].cfg().clone(), &self.config.humans, &self.ports(i))
+    }
+
+    /// Converts a point on the floor of a module, in module space, to world space
+    pub fn module_to_world(&self, i: usize, point: Vec2<f32>) -> Vec3<T> {
+        let module = &self.modules[i];
+        let extent = module.cfg().extent();
+        let arc = module.arc();
+        let to_t = |v: f32| T::from_f64(v as f64).unwrap();
+
+        // Modules are centred across the arc, and stretched along it to fill its length
+        let two = T::one() + T::one();
+        let x = to_t(point.x - extent.w / 2.) + arc.size().x / two;
+        let z = to_t(point.y / extent.h) * self.torus.arc_length(arc);
+        self.torus.vec3_to_world(Vec3::new(x, T::zero(), z), arc)
+    }
+
+    /// A path found within a single module, in world space
+    pub fn path_to_world(&self, i: usize, path: &Path) -> Vec<Vec3<T>> {
+        path.points.iter().map(|(_, point)| self.module_to_world(i, *point)).collect()
+    }
+
+    /// Writes a module's navmesh as a Wavefront OBJ, bent round the ring into world space
+    pub fn navmesh_obj(&self, i: usize, mesh: &NavMesh) -> String {
+        // Fine enough that the chord across each strip stays close to the curve of the ring
+        let step = 1.;
+        mesh.to_obj_with(step, |p| self.module_to_world(i, p).map(|v| v.to_f64().unwrap()))
+    }
+
+    /// The cheapest walk between two rooms anywhere on the ring, given every module's generated rooms in ring order.
+    /// Rooms are given as (module, room), and modules are joined through their boundary ports. An address outside the
+    /// given modules, or a set of modules that is not the whole ring, finds no path.
+    pub fn find_path(&self, modules: &[ModuleFactory], from: (usize, usize), to: (usize, usize)) -> Option<StationPath<T>> {
+        let room = |(module, room): (usize, usize)| modules.get(module).and_then(|m| m.rooms.get(room));
+        if modules.len() != self.modules.len() {
+            return None;
+        }
+        let (first, last) = (room(from)?, room(to)?);
+        let costs = &self.config.paths;
+        let mut offsets = Vec::new();
+        let mut crossings = Vec::new();
+        let mut rooms = 0;
+        for module in modules.iter() {
+            offsets.push(rooms);
+            for crossing in module.crossings(costs) {
+                crossings.push(Crossing { rooms: [crossing.rooms[0] + rooms, crossing.rooms[1] + rooms], ..crossing });
+            }
+            rooms += module.rooms.len();
+        }
+
+        // Each spinward port opens onto the antispinward port of the next module round
+        let n = modules.len();
+        for (i, module) in modules.iter().enumerate() {
+            let next = (i + 1) % n;
+            let spinward = module.ports.iter().find(|p| p.boundary.edge == Compass::North);
+            let antispinward = modules[next].ports.iter().find(|p| p.boundary.edge == Compass::South);
+            if let (Some(a), Some(b)) = (spinward, antispinward) {
+                crossings.push(Crossing {
+                    rooms: [offsets[i] + a.room, offsets[next] + b.room],
+                    positions: [a.position, b.position],
+                    weight: costs.door(),
+                    climb: 0.,
+                });
+            }
+        }
+
+        // Modules without rooms share their offset with the next, so take the last module starting at or before the room
+        let module_of = |room: usize| offsets.partition_point(|o| *o <= room) - 1;
+        let start = (offsets[from.0] + from.1, first.centre());
+        let end = (offsets[to.0] + to.1, last.centre());
+        // Points in different modules are not in the same space, so there is nothing to estimate from
+        let found = path::find(&crossings, rooms, start, end, |_, _| 0.)?;
+
+        Some(StationPath {
+            rooms: found.rooms.iter().map(|r| (module_of(*r), r - offsets[module_of(*r)])).collect(),
+            world: found.points.iter().map(|(r, point)| self.module_to_world(module_of(*r), *point)).collect(),
+            cost: found.cost,
+        })
+    }
+
+    /// The port between module `a` and the next module spinward, `b`, as seen from the module whose edge is given.
+    /// Both modules derive it from their addresses alone, so they always agree on where it is.
+    fn boundary_port(&self, a: usize, b: usize, edge: Compass) -> BoundaryPort {
+        let (cfg_a, cfg_b) = (self.modules[a].cfg(), self.modules[b].cfg());
+        let humans = &self.config.humans;
+        let width = humans.width();
+        let clearance = cfg_a.door_clearance(humans).max(cfg_b.door_clearance(humans));
+
+        // Modules are centred on each other, so the door has to fit within the narrower of the two
+        let span = cfg_a.extent().w.min(cfg_b.extent().w) / 2. - clearance / 2.;
+        let mut rng = self.config.station.seed().child("boundary_ports").index(self.modules[a].uid() as u64).rng();
+        let offset = if span > 0. { rng.gen_range(-span, span) } else { 0. };
+
+        let neighbour = if edge == Compass::North { b } else { a };
+        BoundaryPort {
+            edge,
+            offset,
+            width,
+            neighbour: self.modules[neighbour].address(),
+        }
+    }
 }
 
 #[test]
 fn station_test() {
-    let cfg = Config::import("assets/World.toml");
-    let _station = Station::<f64>::new(cfg);
+    let cfg = Config::import("assets/World.toml").unwrap();
+    let station = Station::<f64>::new(cfg).unwrap();
+
+    let n = station.module_count();
+    for i in 0..n {
+        assert_eq!(station.module(i).uid(), i);
+        assert_eq!(station.module(i).address(), ModuleAddress::from_ring_index(i, 3));
+    }
+
+    // Neighbours agree on every port, all the way round the ring
+    for i in 0..n {
+        let next = (i + 1) % n;
+        let [_, spinward] = station.ports(i);
+        let [antispinward, _] = station.ports(next);
+        assert_eq!(spinward.offset, antispinward.offset);
+        assert_eq!(spinward.neighbour, station.module(next).address());
+        assert_eq!(antispinward.neighbour, station.module(i).address());
+    }
+}
+
+#[test]
+fn station_path_test() {
+    let cfg = Config::from_args(vec!["--preset".to_string(), "small_outpost".to_string()]).unwrap();
+    let station = Station::<f64>::new(cfg).unwrap();
+    let modules: Vec<ModuleFactory> = (0..station.module_count()).map(|i| station.generate_module(i)).collect();
+
+    // Halfway round the ring, so the path has to cross every module in between
+    let to = (station.module_count() / 2, modules[station.module_count() / 2].rooms.len() - 1);
+    let path = station.find_path(&modules, (0, 0), to).unwrap();
+    assert!(station.find_path(&modules, (0, 0), (station.module_count(), 0)).is_none());
+    assert!(station.find_path(&modules, (0, modules[0].rooms.len()), to).is_none());
+    assert!(station.find_path(&modules[1..], (0, 0), (1, 0)).is_none());
+    assert_eq!(path.rooms.first(), Some(&(0, 0)));
+    assert_eq!(path.rooms.last(), Some(&to));
+    assert_eq!(path.world.len(), path.rooms.len() + 1);
+    for pair in path.rooms.windows(2) {
+        let ((ma, a), (mb, b)) = (pair[0], pair[1]);
+        if ma == mb {
+            assert!(modules[ma].rooms[a].is_linked(b));
+        } else {
+            // Crossing into a neighbouring module, through its ports
+            assert!(modules[ma].ports.iter().any(|p| p.room == a));
+            assert!(modules[mb].ports.iter().any(|p| p.room == b));
+        }
+    }
+
+    // Navmeshes can be bent round the ring
+    let mesh = modules[0].navmesh(&station.config.humans);
+    let obj = station.navmesh_obj(0, &mesh);
+    assert!(obj.lines().filter(|l| l.starts_with("v ")).count() > 4 * mesh.polys.len());
+
+    // Ports are where both modules agree they are, in world space as well
+    let (a, b) = (&modules[0].ports[1], &modules[1].ports[0]);
+    let distance = station.module_to_world(0, a.position).distance(station.module_to_world(1, b.position));
+    assert!(distance < 1e-3, "ports are {}m apart", distance);
+}
+
+#[test]
+fn station_ports_test() {
+    let cfg = Config::from_args(vec!["--preset".to_string(), "small_outpost".to_string()]).unwrap();
+    let station = Station::<f64>::new(cfg).unwrap();
+
+    // Within each module both ports can reach each other, so the ring is one continuous graph
+    for i in 0..station.module_count() {
+        let module = station.generate_module(i);
+        module.assert_connected();
+        assert_eq!(module.ports.len(), 2);
+        for port in module.ports.iter() {
+            let room = &module.rooms[port.room].rect;
+            assert!(port.position.x - port.boundary.width / 2. >= room.min.x);
+            assert!(port.position.x + port.boundary.width / 2. <= room.max.x);
+            assert!(port.position.y == room.min.y || port.position.y == room.max.y);
+        }
+    }
 }
\ No newline at end of file